@@ -0,0 +1,229 @@
+use crate::linked_list::NodeMeta;
+use crate::{DefaultState, HashMap};
+use std::hash::{BuildHasher, Hash};
+use std::slice;
+
+// read-only version of HashMap keeping all the pairs in one flat Vec,
+// bucket `i` owns entries[offsets[i]..offsets[i + 1]]; the insertion stamps,
+// hit counts and tracking modes are kept for unfreeze, but reads here aren't counted
+pub struct FrozenHashMap<K, V, S = DefaultState> {
+    entries: Vec<(K, V)>,
+    offsets: Vec<usize>,
+    hasher: S,
+    // handed back to the map on unfreeze
+    load_factor: f32,
+    next_stamp: Option<u64>,
+    track_hits: bool,
+    // the bookkeeping of entries[i] at metas[i], empty if no pair had any
+    metas: Vec<Option<NodeMeta>>,
+}
+
+impl<K, V, S> FrozenHashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.bucket(key)
+            .iter()
+            .find(|(k, _v)| k == key)
+            .map(|(_k, v)| v)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, (K, V)> {
+        self.entries.iter()
+    }
+
+    pub fn size(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn unfreeze(self) -> HashMap<K, V, S> {
+        HashMap::from(self)
+    }
+
+    fn bucket(&self, key: &K) -> &[(K, V)] {
+        // same index as the HashMap this one was frozen from
+        let buckets = self.offsets.len() - 1;
+        if buckets == 0 {
+            return &[];
+        }
+        let index = self.hasher.hash_one(key) as usize % buckets;
+        &self.entries[self.offsets[index]..self.offsets[index + 1]]
+    }
+}
+
+impl<K, V, S> From<HashMap<K, V, S>> for FrozenHashMap<K, V, S> {
+    fn from(map: HashMap<K, V, S>) -> Self {
+        let mut entries = Vec::with_capacity(map.size);
+        let mut metas = Vec::with_capacity(map.size);
+        let mut offsets = Vec::with_capacity(map.table.len() + 1);
+        offsets.push(0);
+        for mut list in map.table {
+            while let Some(node) = list.pop_node() {
+                let (pair, meta) = node.into_parts();
+                entries.push(pair);
+                metas.push(meta);
+            }
+            offsets.push(entries.len());
+        }
+        if metas.iter().all(Option::is_none) {
+            metas = Vec::new();
+        }
+        Self {
+            entries,
+            offsets,
+            hasher: map.hasher,
+            load_factor: map.load_factor,
+            next_stamp: map.next_stamp,
+            track_hits: map.track_hits,
+            metas,
+        }
+    }
+}
+
+// chains the pairs back into linked lists, keeping the amount of buckets
+// and what the map tracked before it was frozen
+impl<K, V, S> From<FrozenHashMap<K, V, S>> for HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    fn from(frozen: FrozenHashMap<K, V, S>) -> Self {
+        let mut map = HashMap::with_hasher(frozen.hasher);
+        map.load_factor = frozen.load_factor;
+        map.threshold = map.threshold_for(map.capacity);
+        map.next_stamp = frozen.next_stamp;
+        map.track_hits = frozen.track_hits;
+        let buckets = frozen.offsets.len() - 1;
+        if buckets == 0 {
+            return map;
+        }
+        map.rehash(buckets);
+        let mut entries = frozen.entries;
+        let mut metas = frozen.metas;
+        // walk the buckets from the last one, so every drain takes exactly one bucket,
+        // and push each bucket from its tail to keep the order
+        for index in (0..buckets).rev() {
+            let start = frozen.offsets[index];
+            let mut bucket_metas = if metas.is_empty() {
+                Vec::new()
+            } else {
+                metas.split_off(start)
+            };
+            for pair in entries.drain(start..).rev() {
+                map.table[index].push_with_meta(pair, bucket_metas.pop().flatten());
+                map.size += 1;
+            }
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::HashMap;
+
+    #[test]
+    fn freeze() {
+        let mut map = HashMap::new();
+        for i in 0..50 {
+            map.put(i, i * 7);
+        }
+        let expected: Vec<Option<i32>> = (0..60).map(|i| map.get(&i).copied()).collect();
+
+        let frozen = map.freeze();
+
+        let found: Vec<Option<i32>> = (0..60).map(|i| frozen.get(&i).copied()).collect();
+        assert_eq!(found, expected);
+        assert_eq!(frozen.size(), 50);
+        assert!(frozen.contains_key(&49));
+        assert!(!frozen.contains_key(&50));
+        assert_eq!(frozen.iter().count(), 50);
+
+        let empty = HashMap::<i32, i32>::new().freeze();
+        assert_eq!(empty.get(&1), None);
+    }
+
+    #[test]
+    fn unfreeze() {
+        let mut map = HashMap::new();
+        for i in 0..20 {
+            map.put(i, i.to_string());
+        }
+        let buckets = map.table.len();
+        let layout: Vec<Vec<(i32, String)>> = map
+            .table
+            .iter()
+            .map(|list| list.iter().cloned().collect())
+            .collect();
+
+        let mut map = HashMap::from(map.freeze());
+
+        let unfrozen: Vec<Vec<(i32, String)>> = map
+            .table
+            .iter()
+            .map(|list| list.iter().cloned().collect())
+            .collect();
+        assert_eq!(unfrozen, layout);
+        assert_eq!(map.table.len(), buckets);
+        assert_eq!(map.size(), 20);
+
+        map.put(20, "20".to_string());
+        assert_eq!(map.remove(&0), Some("0".to_string()));
+        assert_eq!(map.get(&20), Some(&"20".to_string()));
+        assert_eq!(map.freeze().unfreeze().size(), 20);
+    }
+
+    #[test]
+    fn unfreeze_keeps_load_factor() {
+        let mut map = HashMap::with_load_factor(0.5);
+        for i in 0..6 {
+            map.put(i, i);
+        }
+        assert_eq!(map.table.len(), 16);
+
+        let mut map = map.freeze().unfreeze();
+
+        assert_eq!(map.load_factor, 0.5);
+        assert_eq!(map.threshold, 8);
+        for i in 6..9 {
+            map.put(i, i);
+        }
+        assert_eq!(map.table.len(), 32);
+        assert_eq!(
+            HashMap::<i32, i32>::with_load_factor(0.5)
+                .freeze()
+                .unfreeze()
+                .threshold,
+            4
+        );
+    }
+
+    #[test]
+    fn unfreeze_keeps_tracking() {
+        let mut map = HashMap::with_insertion_order();
+        for i in 0..10 {
+            map.put(i, i);
+        }
+        map.set_hit_tracking(true);
+        map.get(&4);
+        map.get(&4);
+
+        let mut map = map.freeze().unfreeze();
+        map.put(100, 0);
+        map.get(&7);
+
+        let order: Vec<i32> = map
+            .iter_by_insertion_order()
+            .into_iter()
+            .map(|(k, _v)| *k)
+            .collect();
+        assert_eq!(order, (0..10).chain([100]).collect::<Vec<_>>());
+        assert_eq!(map.hot_keys(3), vec![&4, &7]);
+    }
+}
@@ -1,48 +1,147 @@
+mod fnv;
+mod frozen;
 mod linked_list;
 
-use crate::linked_list::{Iter as IterLL, LinkedList};
-use std::fmt::Debug;
+pub use crate::fnv::{FnvBuildHasher, FnvHasher};
+pub use crate::frozen::FrozenHashMap;
+use crate::linked_list::{
+    IntoIter as IntoIterLL, Iter as IterLL, IterMut as IterMutLL, LinkedList, Node,
+};
+use std::fmt::{self, Debug};
 use std::mem;
+use std::ops::{AddAssign, Index, IndexMut};
+use std::sync::mpsc::{SendError, Sender};
 use std::{
-    collections::hash_map::DefaultHasher,
-    hash::{Hash, Hasher},
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hash},
 };
 
 const DEFAULT_CAPACITY: usize = 8;
 const DEFAULT_LOAD_FACTOR: f32 = 0.75;
+// share of empty buckets past which a churned table is worth compacting
+const COMPACT_EMPTY_RATIO: f32 = 0.75;
+// grow_until never doubles the table past this amount of buckets
+const MAX_GROW_CAPACITY: usize = 1 << 24;
+const REPORT_BAR_WIDTH: usize = 40;
+
+// seeded per map, so keys can't be picked to pile up in one bucket
+type DefaultState = RandomState;
 
-#[derive(Debug)]
-pub struct HashMap<K, V> {
+pub struct HashMap<K, V, S = DefaultState> {
     pub table: Vec<LinkedList<(K, V)>>,
     // amount of pairs
     size: usize,
-    _load_factor: f32,
+    // share of `capacity` the size may reach before the table doubles, in (0, 1]
+    load_factor: f32,
     capacity: usize,
     // hash_map will double its capacity when this variable will be reached
     threshold: usize,
+    // amount of pairs removed since the table was last rebuilt
+    churn: usize,
+    // sequence number for the next new pair, None unless insertion order is tracked
+    next_stamp: Option<u64>,
+    hasher: S,
+    // called with the old and the new amount of buckets after every resize
+    resize_hook: Option<ResizeHook>,
+    // whether `get` bumps the read counter of the pair it returns, see `hot_keys`
+    track_hits: bool,
 }
 
+// Sync too, so a map without a hook stays shareable behind Arc<RwLock<..>>
+type ResizeHook = Box<dyn FnMut(usize, usize) + Send + Sync>;
+
 impl<K, V> HashMap<K, V>
 where
-    K: Hash + Eq + PartialEq, //+ Debug + Clone,
-    V: Debug,
+    K: Hash + Eq,
 {
     pub fn new() -> Self {
+        Self::with_hasher(Default::default())
+    }
+
+    // allocates the buckets right away, enough of them that `cap` pairs fit under the
+    // threshold, so filling the map up to `cap` never resizes
+    pub fn with_capacity(cap: usize) -> Self {
+        let mut map = Self::new();
+        map.rehash(map.capacity_for(cap));
+        map
+    }
+
+    // a lower load factor trades memory for shorter chains
+    pub fn with_load_factor(lf: f32) -> Self {
+        assert!(
+            lf > 0.0 && lf <= 1.0,
+            "load factor must be in (0.0, 1.0], got {}",
+            lf
+        );
+        let mut map = Self::new();
+        map.load_factor = lf;
+        map.threshold = map.threshold_for(map.capacity);
+        map
+    }
+
+    // every new pair records an increasing sequence number, see `iter_by_insertion_order`
+    pub fn with_insertion_order() -> Self {
+        Self {
+            next_stamp: Some(0),
+            ..Self::new()
+        }
+    }
+}
+
+impl<K, V> HashMap<K, V, FnvBuildHasher>
+where
+    K: Hash + Eq,
+{
+    pub fn with_fnv_hasher() -> Self {
+        Self::with_hasher(FnvBuildHasher::default())
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn with_hasher(hasher: S) -> Self {
         Self {
             table: Vec::new(),
             size: 0,
             capacity: DEFAULT_CAPACITY,
-            _load_factor: DEFAULT_LOAD_FACTOR,
+            load_factor: DEFAULT_LOAD_FACTOR,
             threshold: (DEFAULT_CAPACITY as f32 * DEFAULT_LOAD_FACTOR) as usize,
+            churn: 0,
+            next_stamp: None,
+            hasher,
+            resize_hook: None,
+            track_hits: false,
         }
     }
 
-    pub fn put(&mut self, new_key: K, new_value: V) -> Option<V> {
-        if self.table.len() == 0 || self.size >= self.threshold {
-            self.resize();
+    // places every (hash, key, value) by the given hash without hashing the key again,
+    // the table is sized once up front; later triples overwrite earlier ones with the
+    // same key. The hashes must come from `hasher` (e.g. via `hash_of` on a map sharing
+    // it), otherwise lookups never find the pairs; only debug builds check that
+    pub fn from_prehashed(entries: Vec<(u64, K, V)>, hasher: S) -> Self {
+        let mut map = Self::with_hasher(hasher);
+        map.rehash(map.capacity_for(entries.len()));
+        let buckets = map.table.len();
+        for (hash, k, v) in entries {
+            debug_assert_eq!(hash, map.hash_of(&k), "prehashed with a different hasher");
+            let index = hash as usize % buckets;
+            match map.table[index].iter_mut().find(|(key, _v)| *key == k) {
+                Some(pair) => pair.1 = v,
+                None => map.push_new(index, (k, v)),
+            }
         }
+        map
+    }
+
+    pub fn put(&mut self, new_key: K, new_value: V) -> Option<V> {
+        self.grow_if_needed();
 
-        let index = self.index_for(&new_key);
+        let index = self
+            .index_for(&new_key)
+            .expect("grow_if_needed allocates the table");
 
         // check if map contains particular key
         match self.table[index].iter_mut().find(|(k, _v)| *k == new_key) {
@@ -53,37 +152,131 @@ where
             }
             //if none, push new pair to that existing list
             None => {
-                self.table[index].push((new_key, new_value));
-                self.size += 1;
+                self.push_new(index, (new_key, new_value));
                 None
             }
         }
     }
 
-    pub fn get(&mut self, key: K) -> Option<V> {
-        let mut res = None;
-        let index = self.index_for(&key);
-        let mut new_list = LinkedList::new();
-        let temp = mem::replace(&mut self.table[index], LinkedList::new());
-        temp.into_iter().for_each(|(k, v)| {
-            if k == key {
-                self.size -= 1;
-                res = Some(v);
-            } else {
-                new_list.push((k, v));
+    // puts a clone of every pair, later pairs overwrite earlier ones with the same key
+    pub fn load_entries_from_slice(&mut self, entries: &[(K, V)])
+    where
+        K: Clone,
+        V: Clone,
+    {
+        for (k, v) in entries {
+            self.put(k.clone(), v.clone());
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let node = self.find_node(key)?;
+        if self.track_hits {
+            node.hit();
+        }
+        Some(&node.element().1)
+    }
+
+    // the stored key is handed back too, it may be a different allocation than `key`
+    pub fn get_key_value(&self, key: &K) -> Option<(&K, &V)> {
+        self.find(key).map(|(k, v)| (k, v))
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.find(key).is_some()
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        if self.track_hits {
+            // bumping only needs a shared borrow, so it is done before taking the mutable one
+            self.find_node(key)?.hit();
+        }
+        let index = self.index_for(key)?;
+        self.table[index]
+            .iter_mut()
+            .find(|(k, _v)| k == key)
+            .map(|(_k, v)| v)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let index = self.index_for(key)?;
+        let (_k, v) = self.table[index].remove_first(|(k, _v)| k == key)?;
+        self.removed(1);
+        Some(v)
+    }
+
+    // non-destructive lookup returning an owned copy of the value
+    pub fn get_cloned(&self, key: &K) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.get(key).cloned()
+    }
+
+    // like get_cloned, but an absent key reads as V::default()
+    pub fn get_or_default_cloned(&self, key: &K) -> V
+    where
+        V: Default + Clone,
+    {
+        self.get_cloned(key).unwrap_or_default()
+    }
+
+    // runs `f` on the values of two distinct keys, e.g. to move a balance between them;
+    // returns false when either key is absent or both keys are the same
+    pub fn transfer<F: FnOnce(&mut V, &mut V)>(&mut self, from: &K, to: &K, f: F) -> bool {
+        match self.get_pair_mut_same_bucket(from, to) {
+            Some((a, b)) => {
+                f(a, b);
+                true
             }
-        });
-        if new_list.size() > 0 {
-            self.table[index] = new_list;
+            None => false,
         }
-        res
     }
 
+    // the pair with the biggest value, ties resolve to the first one met by `iter`
+    pub fn most_common(&self) -> Option<(&K, &V)>
+    where
+        V: Ord,
+    {
+        let mut max: Option<&(K, V)> = None;
+        for pair in self.iter() {
+            match max {
+                Some((_k, v)) if pair.1 <= *v => {}
+                _ => max = Some(pair),
+            }
+        }
+        max.map(|(k, v)| (k, v))
+    }
+
+    // kept for existing callers, `len` is the preferred name
     pub fn size(&self) -> usize {
         self.size
     }
 
-    pub fn iter(&self) -> Iter<K, V> {
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        if let Some(index) = self.index_for(&key) {
+            if self.table[index].iter().any(|(k, _v)| *k == key) {
+                return Entry::Occupied(OccupiedEntry {
+                    map: self,
+                    index,
+                    key,
+                });
+            }
+        }
+        // the bucket of a vacant entry is computed only on insert,
+        // because inserting may resize the table first
+        Entry::Vacant(VacantEntry { map: self, key })
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
         let mut iter = None;
         let mut index = 0;
 
@@ -112,188 +305,3042 @@ where
         }
     }
 
-    fn index_for(&self, key: &K) -> usize {
-        let mut hasher = DefaultHasher::new();
-        key.hash(&mut hasher);
-        let hash = hasher.finish();
-        hash as usize % self.table.len()
+    // the pairs in exactly the reverse of the `iter` order; the chains are singly
+    // linked, so every chain is buffered while the buckets are walked back to front
+    pub fn iter_rev(&self) -> impl Iterator<Item = &(K, V)> {
+        self.table.iter().rev().flat_map(|list| list.iter().rev())
     }
 
-    fn resize(&mut self) {
-        match self.table.len() {
-            // default resizing
-            0 => {
-                self.table = (0..DEFAULT_CAPACITY).map(|_| LinkedList::new()).collect();
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys(self.iter())
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values(self.iter())
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            table: self.table.iter_mut(),
+            iter: None,
+        }
+    }
+
+    // push prepends, so every bucket iterates in reverse insertion order;
+    // reversing the chains gives back the insertion order inside each bucket
+    pub fn reinsert_reversed(&mut self) {
+        self.table.iter_mut().for_each(|list| list.reverse());
+    }
+
+    // number of distinct full hashes among the stored keys,
+    // always >= the number of non-empty buckets
+    pub fn count_distinct_hashes(&self) -> usize {
+        let mut hashes: Vec<u64> = self.iter().map(|(k, _v)| self.hash_of(k)).collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+        hashes.len()
+    }
+
+    // gives direct access to every bucket, the caller must not add or drop pairs
+    // through it, otherwise `size` won't match the content anymore
+    pub fn iter_buckets_mut(&mut self) -> impl Iterator<Item = &mut LinkedList<(K, V)>> {
+        self.table.iter_mut()
+    }
+
+    pub fn longest_chain(&self) -> usize {
+        self.table.iter().map(|list| list.size()).max().unwrap_or(0)
+    }
+
+    // multi-line summary of the table: size, capacity, load, longest chain
+    // and a histogram of how many buckets have a chain of each length
+    pub fn capacity_utilization_report(&self) -> String {
+        let longest = self.longest_chain();
+        let load = match self.table.len() {
+            0 => 0.0,
+            n => self.size as f32 / n as f32,
+        };
+        let mut frequencies = vec![0; longest + 1];
+        self.table
+            .iter()
+            .for_each(|list| frequencies[list.size()] += 1);
+
+        let mut report = format!(
+            "size: {}\ncapacity: {}\nload: {:.2}\nlongest chain: {}\nchain lengths:\n",
+            self.size, self.capacity, load, longest
+        );
+        for (len, count) in frequencies.iter().enumerate() {
+            // keep the bars short for big tables
+            let bar = "#".repeat((*count).min(REPORT_BAR_WIDTH));
+            report.push_str(&format!("{:>4} | {} {}\n", len, bar, count));
+        }
+        report
+    }
+
+    // true when removals left a grown table mostly made of empty buckets, see `compact`
+    pub fn should_compact(&self) -> bool {
+        if self.churn == 0 || self.table.len() <= DEFAULT_CAPACITY {
+            return false;
+        }
+        let empty = self.table.iter().filter(|list| list.size() == 0).count();
+        empty as f32 / self.table.len() as f32 > COMPACT_EMPTY_RATIO
+    }
+
+    // shrinks the table to the smallest one holding the current pairs without resizing
+    pub fn compact(&mut self) {
+        self.rehash(self.capacity_for(self.size));
+    }
+
+    // 0.0 to 1.0, half from the share of empty buckets and half from the share of
+    // nodes freed since the last rebuild among all nodes allocated since then;
+    // a high score means `defragment` or `optimize_capacity` is likely to pay off
+    pub fn estimate_memory_fragmentation(&self) -> f64 {
+        if self.table.is_empty() {
+            return 0.0;
+        }
+        let empty = self.table.iter().filter(|list| list.size() == 0).count();
+        let empty_ratio = empty as f64 / self.table.len() as f64;
+        let churn_ratio = match self.churn + self.size {
+            0 => 0.0,
+            allocated => self.churn as f64 / allocated as f64,
+        };
+        0.5 * empty_ratio + 0.5 * churn_ratio
+    }
+
+    // rehashes the pairs into every candidate table size and keeps the one
+    // with the shortest longest chain (the first one on ties), returns its capacity.
+    // Candidates the next put would grow away under the load factor are skipped
+    pub fn optimize_capacity(&mut self, candidates: &[usize]) -> usize {
+        let original = self.table.len();
+        let mut best: Option<(usize, usize)> = None;
+        for &capacity in candidates.iter().filter(|c| **c > 0) {
+            if self.threshold_for(capacity) <= self.size {
+                continue;
             }
-            // when this resize method is called after the threshold is reached
-            n => {
-                self.threshold = ((n * 2) as f32 * DEFAULT_LOAD_FACTOR) as usize;
-                self.capacity *= 2;
-
-                // replace the old table with new doubled one
-                let mut temp = mem::replace(
-                    &mut self.table,
-                    (0..n * 2).map(|_| LinkedList::new()).collect(),
-                );
-                // reinsert the old table's values in the new one
-                for i in 0..temp.len() {
-                    let t = mem::replace(&mut temp[i], LinkedList::new());
-                    t.into_iter().for_each(|pair| {
-                        // minus 1 because actually it's not new pair
-                        self.put(pair.0, pair.1);
-                        self.size -= 1;
-                    });
-                }
+            // trial tables don't count as resizes, only the one kept does
+            self.relink(capacity);
+            let longest = self.longest_chain();
+            match best {
+                Some((_c, l)) if l <= longest => {}
+                _ => best = Some((capacity, longest)),
+            }
+        }
+        if let Some((capacity, _l)) = best {
+            if capacity != self.table.len() {
+                self.relink(capacity);
             }
         }
+        self.resized(original);
+        self.capacity
     }
-}
 
-pub struct Iter<'a, K, V> {
-    index: usize,
-    table: &'a Vec<LinkedList<(K, V)>>,
-    iter: Option<IterLL<'a, (K, V)>>,
-}
-impl<'a, K, V> Iterator for Iter<'a, K, V> {
-    type Item = &'a (K, V);
-    fn next(&mut self) -> Option<Self::Item> {
-        // have to check if there is some iter
-        // because the method iter() could be called on the empty map
-        if let Some(iter) = &mut self.iter {
-            // check whether this iter has a next node or not
-            match iter.next() {
-                // if so, return the reference on its contain
-                Some(pair) => Some(pair),
-                // if not, search a next list in the hash_map's table
-                None => loop {
-                    // break this loop on the end of Vec (table)
-                    if self.index == self.table.len() {
-                        break None;
-                    }
-                    match &self.table[self.index].size() {
-                        // if the list size is 0 that means that it has no any next node
-                        // just increment index of table
-                        0 => {
-                            self.index += 1;
-                        }
-                        _ => {
-                            // if there is some size replace self.iter to its iter next value
-                            // and return the reference to its contained value
-                            self.iter = Some(self.table[self.index].iter());
-                            self.index += 1;
-                            break self.iter.as_mut().unwrap().next();
-                        }
-                    }
-                },
+    // adds `by` to the value of `key`, starting from V::default() for a new key
+    pub fn bump(&mut self, key: K, by: V)
+    where
+        V: Default + AddAssign,
+    {
+        *self.entry(key).or_insert_with(V::default) += by;
+    }
+
+    // runs `modify` on the value of an existing key, a new key gets `default` untouched
+    pub fn upsert<F: FnOnce(&mut V)>(&mut self, key: K, default: V, modify: F) {
+        match self.entry(key) {
+            Entry::Occupied(entry) => modify(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                entry.insert(default);
             }
-        } else {
-            None
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::DEFAULT_CAPACITY;
+    // all pairs ordered by value, biggest first; the sort is stable, so equal values
+    // keep their `iter` order, which itself is arbitrary
+    pub fn sorted_by_value(&self) -> Vec<(&K, &V)>
+    where
+        V: Ord,
+    {
+        let mut pairs: Vec<(&K, &V)> = self.iter().map(|(k, v)| (k, v)).collect();
+        pairs.sort_by(|a, b| b.1.cmp(a.1));
+        pairs
+    }
+
+    // keeps the `n` pairs with the biggest values and removes the rest;
+    // pairs tied with the n-th biggest value are all kept, so more than `n` may survive
+    pub fn retain_top_n(&mut self, n: usize)
+    where
+        V: Ord,
+    {
+        let mut nodes = Vec::with_capacity(self.size);
+        for list in self.table.iter_mut() {
+            while let Some(node) = list.pop_node() {
+                nodes.push(node);
+            }
+        }
+        nodes.sort_by(|a, b| b.element().1.cmp(&a.element().1));
 
-    use super::HashMap;
+        let mut keep = n.min(nodes.len());
+        if keep > 0 {
+            while keep < nodes.len() && nodes[keep].element().1 == nodes[keep - 1].element().1 {
+                keep += 1;
+            }
+        }
+        self.churn += nodes.len() - keep;
+        nodes.truncate(keep);
 
-    #[test]
-    fn basic() {
-        let mut map: HashMap<i32, i32> = HashMap::new();
-        map.put(1, 555);
-        assert_eq!(map.size(), 1);
-        //default capacity
-        assert_eq!(map.table.len(), DEFAULT_CAPACITY);
+        self.size = nodes.len();
+        for node in nodes {
+            // the nodes came out of this table, so it is allocated
+            let index = self.index_for(&node.element().0).unwrap();
+            self.table[index].push_node(node);
+        }
     }
 
-    #[test]
-    fn put() {
-        let mut map: HashMap<String, String> = HashMap::new();
-        assert_eq!(map.size(), 0);
+    // like put, but also hands back a mutable reference to the stored value
+    pub fn insert_tracked(&mut self, key: K, value: V) -> (Option<V>, &mut V) {
+        match self.entry(key) {
+            Entry::Occupied(entry) => {
+                let stored = entry.into_mut();
+                let old = mem::replace(stored, value);
+                (Some(old), stored)
+            }
+            Entry::Vacant(entry) => (None, entry.insert(value)),
+        }
+    }
 
-        map.put("key_1".to_string(), "value_1".to_string());
-        map.put("key_2".to_string(), "value_2".to_string());
-        assert_eq!(map.size(), 2);
+    // pairs with keys in [low, high] sorted by key; the table isn't ordered,
+    // so this scans every pair
+    pub fn range(&self, low: &K, high: &K) -> Vec<(&K, &V)>
+    where
+        K: Ord,
+    {
+        let mut pairs: Vec<(&K, &V)> = self
+            .iter()
+            .filter(|(k, _v)| k >= low && k <= high)
+            .map(|(k, v)| (k, v))
+            .collect();
+        pairs.sort_by(|a, b| a.0.cmp(b.0));
+        pairs
+    }
 
-        let old_value = map.put("key_1".to_string(), "value_2".to_string());
-        assert_eq!(old_value, Some("value_1".to_string()));
-        assert_eq!(map.size(), 2);
+    // mutable references to the values of two distinct keys, also when both keys
+    // share one chain; None if either key is absent or both keys are the same
+    pub fn get_pair_mut_same_bucket(&mut self, k1: &K, k2: &K) -> Option<(&mut V, &mut V)> {
+        if k1 == k2 {
+            return None;
+        }
+        let (i1, i2) = (self.index_for(k1)?, self.index_for(k2)?);
+        if i1 == i2 {
+            // both pairs live in one list, a single iter_mut hands out disjoint references
+            let (mut first, mut second) = (None, None);
+            for (k, v) in self.table[i1].iter_mut() {
+                if k == k1 {
+                    first = Some(v);
+                } else if k == k2 {
+                    second = Some(v);
+                }
+            }
+            first.zip(second)
+        } else {
+            let (low, high) = self.table.split_at_mut(i1.max(i2));
+            let (list_1, list_2) = if i1 < i2 {
+                (&mut low[i1], &mut high[0])
+            } else {
+                (&mut high[0], &mut low[i2])
+            };
+            let first = list_1.iter_mut().find(|(k, _v)| k == k1);
+            let second = list_2.iter_mut().find(|(k, _v)| k == k2);
+            first.zip(second).map(|((_k1, v1), (_k2, v2))| (v1, v2))
+        }
     }
 
-    #[test]
-    fn get() {
-        let mut map = HashMap::new();
+    // pairs in the order they were first put; only meaningful for a map created
+    // with `with_insertion_order`, otherwise every stamp is 0 and the order is `iter`'s
+    pub fn iter_by_insertion_order(&self) -> Vec<(&K, &V)> {
+        let mut nodes: Vec<_> = self.table.iter().flat_map(|list| list.nodes()).collect();
+        nodes.sort_by_key(|node| node.stamp());
+        nodes
+            .into_iter()
+            .map(|node| (&node.element().0, &node.element().1))
+            .collect()
+    }
 
-        map.put("key_1".to_string(), "value_1".to_string());
-        assert_eq!(map.size(), 1);
+    // variance of the chain lengths around the mean load (size / buckets)
+    pub fn bucket_load_variance(&self) -> f64 {
+        if self.table.is_empty() {
+            return 0.0;
+        }
+        let buckets = self.table.len() as f64;
+        let mean = self.size as f64 / buckets;
+        self.table
+            .iter()
+            .map(|list| (list.size() as f64 - mean).powi(2))
+            .sum::<f64>()
+            / buckets
+    }
 
-        let v = map.get("key_1".to_string());
-        let n = map.get("empty".to_string());
-        assert_eq!(v, Some("value_1".to_string()));
-        assert_eq!(n, None);
-        assert_eq!(map.size(), 0);
+    // rebuilds the table with a fresh `S::default()` when the chain lengths vary by more
+    // than `variance_threshold`, returns whether it did; only a randomly seeded `S`
+    // (like RandomState) actually breaks the skew, a fixed one hashes the same again
+    pub fn auto_rehash_if_skewed(&mut self, variance_threshold: f64) -> bool
+    where
+        S: Default,
+    {
+        if self.bucket_load_variance() <= variance_threshold {
+            return false;
+        }
+        self.hasher = S::default();
+        self.rehash(self.table.len());
+        true
     }
 
-    #[test]
-    fn resize() {
-        let mut map = HashMap::new();
-        map.put(1, 1);
-        assert_eq!(map.size(), 1);
-        assert_eq!(map.table.len(), DEFAULT_CAPACITY);
-        map.get(1);
-        assert_eq!(map.size(), 0);
-        assert_eq!(map.table.len(), DEFAULT_CAPACITY);
-        for i in 0..7 {
-            map.put(i, i);
+    // iterates over a copy of the pairs, so the map can be changed (e.g. by another
+    // thread, once the lock guarding it is released) while the snapshot is read
+    pub fn snapshot_iter(&self) -> std::vec::IntoIter<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.iter().cloned().collect::<Vec<_>>().into_iter()
+    }
+
+    // amount of pairs in the buckets [start, end), the range is clamped to the table
+    pub fn entry_count_in_range_of_buckets(&self, start: usize, end: usize) -> usize {
+        let end = end.min(self.table.len());
+        let start = start.min(end);
+        self.table[start..end].iter().map(|list| list.size()).sum()
+    }
+
+    // removes the pair with the smallest value, ties resolve to the first one met
+    pub fn take_min_value_entry(&mut self) -> Option<(K, V)>
+    where
+        V: Ord,
+    {
+        let mut min: Option<(usize, usize, &V)> = None;
+        for (bucket, list) in self.table.iter().enumerate() {
+            for (pos, (_k, v)) in list.iter().enumerate() {
+                match min {
+                    Some((_b, _p, m)) if v >= m => {}
+                    _ => min = Some((bucket, pos, v)),
+                }
+            }
         }
-        assert_eq!(map.size(), 7);
-        assert_eq!(map.table.len(), DEFAULT_CAPACITY * 2);
-        for i in 7..16 {
-            map.put(i, i);
+        let (bucket, pos, _v) = min?;
+        let pair = self.table[bucket].remove_at(pos);
+        self.removed(1);
+        pair
+    }
+
+    // keeps doubling the table until `done` returns true or the table reaches
+    // MAX_GROW_CAPACITY buckets
+    pub fn grow_until<F: Fn(&Self) -> bool>(&mut self, done: F) {
+        while !done(self) && self.table.len() < MAX_GROW_CAPACITY {
+            self.resize();
         }
-        assert_eq!(map.size(), 16);
-        assert_eq!(map.table.len(), DEFAULT_CAPACITY * 4);
     }
 
-    #[test]
-    fn iter() {
-        let mut map = HashMap::new();
+    // (bucket index, chain length) of every non-empty bucket
+    pub fn each_bucket_stats(&self) -> Vec<(usize, usize)> {
+        self.table
+            .iter()
+            .enumerate()
+            .filter(|(_i, list)| list.size() > 0)
+            .map(|(i, list)| (i, list.size()))
+            .collect()
+    }
 
-        let mut pairs_count = 0;
+    pub fn consume_into_pairs_sorted(self) -> Vec<(K, V)>
+    where
+        K: Ord,
+    {
+        let mut pairs: Vec<(K, V)> = self.into_iter().collect();
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+        pairs
+    }
 
-        for (k, v) in map.iter() {
-            pairs_count += 1;
+    // rehashes every pair into a table of exactly `new_cap` buckets, which doesn't
+    // have to be a power of two since the bucket index is a plain modulo
+    pub fn replace_table_with_capacity(&mut self, new_cap: usize) {
+        assert!(new_cap > 0, "the table needs at least one bucket");
+        self.rehash(new_cap);
+    }
+
+    // looks for `key` in the given bucket only, not in the one it hashes to;
+    // helps to find pairs misplaced by direct edits of the table
+    pub fn key_exists_in_bucket_only(&self, key: &K, bucket: usize) -> bool {
+        self.table
+            .get(bucket)
+            .is_some_and(|list| list.iter().any(|(k, _v)| k == key))
+    }
+
+    // rebuilds every chain from fresh allocations made one after another, the order
+    // of the pairs, their stamps and their hit counts stay the same
+    pub fn defragment(&mut self) {
+        for list in self.table.iter_mut() {
+            let mut pairs = Vec::with_capacity(list.size());
+            while let Some(node) = list.pop_node() {
+                pairs.push(node.into_parts());
+            }
+            // the old nodes are all freed by now, rebuild from the tail to keep the order
+            while let Some((pair, meta)) = pairs.pop() {
+                list.push_with_meta(pair, meta);
+            }
         }
+    }
 
-        assert_eq!(pairs_count, 0);
+    // one Vec of pairs per non-empty bucket, e.g. as work units for a thread pool
+    pub fn iter_chunks_by_bucket(&self) -> impl Iterator<Item = Vec<(&K, &V)>> {
+        self.table
+            .iter()
+            .filter(|list| list.size() > 0)
+            .map(|list| list.iter().map(|(k, v)| (k, v)).collect())
+    }
 
-        map.put("a", 17);
-        map.put("b", 78);
-        map.put("c", 777);
+    // removes `key` and also reports how many pairs are left in its bucket
+    pub fn remove_with_bucket_len(&mut self, key: &K) -> Option<(V, usize)> {
+        let v = self.remove(key)?;
+        Some((v, self.table[self.index_for(key)?].size()))
+    }
 
-        for (k, v) in map.iter() {
-            match k {
-                &"a" => assert_eq!(*v, 17),
-                &"b" => assert_eq!(*v, 78),
-                &"c" => assert_eq!(*v, 777),
-                _ => unreachable!(),
-            }
-            pairs_count += 1;
+    // unlinks the pair at position `pos` (0 is the head) of bucket `bucket`,
+    // e.g. one spotted through `each_bucket_stats` or `iter_chunks_by_bucket`
+    pub fn remove_at(&mut self, bucket: usize, pos: usize) -> Option<(K, V)> {
+        let pair = self.table.get_mut(bucket)?.remove_at(pos)?;
+        self.removed(1);
+        Some(pair)
+    }
+
+    // new map with clones of the pairs matching `f`, its table is sized for the
+    // subset up front
+    pub fn clone_subset<F: Fn(&K, &V) -> bool>(&self, f: F) -> Self
+    where
+        K: Clone,
+        V: Clone,
+        S: Clone,
+    {
+        let pairs: Vec<&(K, V)> = self.iter().filter(|(k, v)| f(k, v)).collect();
+        let mut subset = Self::with_hasher(self.hasher.clone());
+        subset.load_factor = self.load_factor;
+        subset.rehash(subset.capacity_for(pairs.len()));
+        for (k, v) in pairs {
+            subset.put(k.clone(), v.clone());
         }
+        subset
+    }
 
-        assert_eq!(pairs_count, 3);
+    // `clone_subset` under the name of the iterator chain it stands for,
+    // `iter().filter(..).cloned().collect()` into a map with the same hasher
+    pub fn filter_collect_to_map<F: Fn(&K, &V) -> bool>(&self, pred: F) -> Self
+    where
+        K: Clone,
+        V: Clone,
+        S: Clone,
+    {
+        self.clone_subset(pred)
+    }
+
+    // false if some key is stored more than once, e.g. after direct edits of `table`;
+    // equal keys hash equally, so only keys sharing a full hash are compared
+    pub fn verify_no_duplicate_keys(&self) -> bool {
+        let mut keys: Vec<(u64, &K)> = self.iter().map(|(k, _v)| (self.hash_of(k), k)).collect();
+        keys.sort_by_key(|(hash, _k)| *hash);
+        keys.chunk_by(|a, b| a.0 == b.0).all(|same_hash| {
+            same_hash
+                .iter()
+                .enumerate()
+                .all(|(i, (_h, k))| same_hash[i + 1..].iter().all(|(_h, other)| other != k))
+        })
+    }
+
+    // the full 64 bit hash of `key` before it's reduced to a bucket index,
+    // for building sharding or consistent hashing on top of the map
+    pub fn hash_of(&self, key: &K) -> u64 {
+        self.hasher.hash_one(key)
+    }
+
+    // registers a callback fired with (old, new) amount of buckets whenever
+    // the table is reallocated, replacing any previous one
+    pub fn on_resize(&mut self, hook: ResizeHook) {
+        self.resize_hook = Some(hook);
+    }
+
+    // membership of every key, in the order of `keys`
+    pub fn batch_contains(&self, keys: &[K]) -> Vec<bool> {
+        keys.iter().map(|key| self.contains_key(key)).collect()
+    }
+
+    // runs `f` on every pair, the values can be changed in place
+    pub fn apply<F: FnMut(&K, &mut V)>(&mut self, mut f: F) {
+        self.table
+            .iter_mut()
+            .flat_map(|list| list.iter_mut())
+            .for_each(|(k, v)| f(k, v));
+    }
+
+    // removes every pair holding `value`, returns how many were removed
+    pub fn remove_matching_value(&mut self, value: &V) -> usize
+    where
+        V: PartialEq,
+    {
+        let before = self.size;
+        self.retain(|_k, v| v != value);
+        before - self.size
+    }
+
+    // puts every pair of `list`, e.g. one taken with `steal_bucket`, into the bucket
+    // its key hashes to; later pairs overwrite earlier ones with the same key
+    pub fn put_all_from_list(&mut self, list: LinkedList<(K, V)>) {
+        for (k, v) in list.into_iter() {
+            self.put(k, v);
+        }
     }
 
-    // #[test]
-    // fn into_iter() {
-    //     let mut map = HashMap::new();
-    //     map.put("one", 1);
-    //     map.put("two", 2);
-    //     map.put("three", 3);
-    //
-    //     let res: Vec<(&str, i32)> = map.into_iter().collect();
-    //
-    //     assert_eq!(res, vec![("three", 3), ("two", 2), ("one", 1)]);
-    // }
+    // keeps only the pairs for which `f` returns true, in a single pass over the chains;
+    // `f` may change the values on the way
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        let mut removed = 0;
+        for list in self.table.iter_mut() {
+            let len = list.size();
+            list.retain_mut(|(k, v)| f(k, v));
+            removed += len - list.size();
+        }
+        self.removed(removed);
+    }
+
+    // read-only, flat copy of the map for read heavy workloads
+    pub fn freeze(self) -> FrozenHashMap<K, V, S> {
+        FrozenHashMap::from(self)
+    }
+
+    // off by default, so the nodes carry no counters; switching it on gives every
+    // pair one, switching it off keeps the counts but stops them from growing
+    pub fn set_hit_tracking(&mut self, enabled: bool) {
+        self.track_hits = enabled;
+        if !enabled {
+            return;
+        }
+        for list in self.table.iter_mut() {
+            let mut node = list.head_node_mut();
+            while let Some(n) = node {
+                n.track();
+                node = n.next_mut();
+            }
+        }
+    }
+
+    // the `n` keys read most often through `get`, most read first; keys never read are left out
+    pub fn hot_keys(&self, n: usize) -> Vec<&K> {
+        let mut nodes: Vec<_> = self
+            .table
+            .iter()
+            .flat_map(|bucket| bucket.nodes())
+            .filter(|node| node.hits() > 0)
+            .collect();
+        nodes.sort_by_key(|node| std::cmp::Reverse(node.hits()));
+        nodes
+            .into_iter()
+            .take(n)
+            .map(|node| &node.element().0)
+            .collect()
+    }
+
+    // regroups the pairs under `project(key)`, values landing on the same group are
+    // combined with `merge(earlier, later)` in arbitrary order
+    pub fn collapse_by<G, F, M>(self, project: F, merge: M) -> HashMap<G, V>
+    where
+        G: Hash + Eq,
+        F: Fn(&K) -> G,
+        M: Fn(V, V) -> V,
+    {
+        let mut collapsed = HashMap::new();
+        for (k, v) in self {
+            let group = project(&k);
+            let merged = match collapsed.remove(&group) {
+                Some(earlier) => merge(earlier, v),
+                None => v,
+            };
+            collapsed.put(group, merged);
+        }
+        collapsed
+    }
+
+    // put that refuses a new key when its bucket already holds `max_chain` pairs,
+    // replacing the value of a present key always succeeds
+    pub fn bounded_insert(
+        &mut self,
+        key: K,
+        value: V,
+        max_chain: usize,
+    ) -> Result<Option<V>, ChainTooLong> {
+        self.grow_if_needed();
+        let index = self
+            .index_for(&key)
+            .expect("grow_if_needed allocates the table");
+        if let Some(pair) = self.table[index].iter_mut().find(|(k, _v)| *k == key) {
+            return Ok(Some(mem::replace(&mut pair.1, value)));
+        }
+        if self.table[index].size() >= max_chain {
+            return Err(ChainTooLong {
+                bucket: index,
+                max_chain,
+            });
+        }
+        self.push_new(index, (key, value));
+        Ok(None)
+    }
+
+    // hands every pair to `tx`, e.g. for a consumer on another thread; stops at the
+    // first failed send, which gives the pair back inside the error
+    pub fn entries_into_channel(self, tx: &Sender<(K, V)>) -> Result<(), SendError<(K, V)>> {
+        self.into_iter().try_for_each(|pair| tx.send(pair))
+    }
+
+    // drops every pair but keeps the buckets, so refilling up to the old size won't resize
+    pub fn clear(&mut self) {
+        let cleared = self.size;
+        self.table
+            .iter_mut()
+            .for_each(|list| *list = LinkedList::new());
+        self.removed(cleared);
+    }
+
+    pub fn count_where_value<F: Fn(&V) -> bool>(&self, f: F) -> usize {
+        self.values().filter(|v| f(v)).count()
+    }
+
+    // takes bucket `i` out of the table, leaving an empty list in its place;
+    // an index past the table gives an empty list
+    pub fn steal_bucket(&mut self, i: usize) -> LinkedList<(K, V)> {
+        let Some(bucket) = self.table.get_mut(i) else {
+            return LinkedList::new();
+        };
+        let stolen = mem::replace(bucket, LinkedList::new());
+        self.removed(stolen.size());
+        stolen
+    }
+
+    // the current size as a mark to compare against later, see `delta_since`
+    pub fn mark(&self) -> usize {
+        self.size
+    }
+
+    // net amount of pairs added (positive) or removed (negative) since `mark` was taken
+    pub fn delta_since(&self, mark: usize) -> isize {
+        self.size as isize - mark as isize
+    }
+
+    // makes room for `additional` more pairs with at most one rehash, so inserting
+    // them won't resize; allocates the table of a map that has none yet
+    pub fn reserve(&mut self, additional: usize) {
+        if additional == 0 {
+            return;
+        }
+        let needed = self
+            .size
+            .checked_add(additional)
+            .expect("capacity overflow");
+        if self.table.is_empty() || self.threshold_for(self.table.len()) < needed {
+            self.rehash(self.capacity_for(needed));
+        }
+    }
+
+    // (keys with an even full hash, keys with an odd one); both halves share the
+    // hasher, so `hash_of` on either of them tells where a key belongs
+    pub fn split_by_hash_parity(self) -> (Self, Self)
+    where
+        S: Clone,
+    {
+        let mut even = Self::with_hasher(self.hasher.clone());
+        let mut odd = Self::with_hasher(self.hasher.clone());
+        even.load_factor = self.load_factor;
+        odd.load_factor = self.load_factor;
+        for (k, v) in self {
+            let half = if even.hash_of(&k).is_multiple_of(2) {
+                &mut even
+            } else {
+                &mut odd
+            };
+            half.put(k, v);
+        }
+        (even, odd)
+    }
+
+    fn find(&self, key: &K) -> Option<&(K, V)> {
+        self.find_node(key).map(|node| node.element())
+    }
+
+    fn find_node(&self, key: &K) -> Option<&Node<(K, V)>> {
+        let index = self.index_for(key)?;
+        self.table[index]
+            .nodes()
+            .find(|node| node.element().0 == *key)
+    }
+
+    // None while the table isn't allocated yet, no key can be stored then
+    fn index_for(&self, key: &K) -> Option<usize> {
+        match self.table.len() {
+            0 => None,
+            buckets => Some(self.hash_of(key) as usize % buckets),
+        }
+    }
+
+    // allocate the table on first use or double it once the threshold is reached
+    fn grow_if_needed(&mut self) {
+        if self.table.is_empty() || self.size >= self.threshold {
+            self.resize();
+        }
+    }
+
+    // smallest power of two table (at least DEFAULT_CAPACITY) holding `n` pairs without resizing
+    fn capacity_for(&self, n: usize) -> usize {
+        let mut capacity = DEFAULT_CAPACITY;
+        while self.threshold_for(capacity) < n {
+            capacity = capacity.checked_mul(2).expect("capacity overflow");
+        }
+        capacity
+    }
+
+    fn threshold_for(&self, capacity: usize) -> usize {
+        (capacity as f32 * self.load_factor) as usize
+    }
+
+    // bookkeeping after `n` pairs were unlinked, saturating like LinkedList::pop
+    // so a drifted size can't underflow
+    fn removed(&mut self, n: usize) {
+        self.size = self.size.saturating_sub(n);
+        self.churn += n;
+    }
+
+    fn push_new(&mut self, index: usize, pair: (K, V)) {
+        match self.next_stamp.as_mut() {
+            Some(stamp) => {
+                self.table[index].push_stamped(pair, *stamp);
+                *stamp += 1;
+            }
+            // the stamp is unused then, 0 is what an untracked node reports as well
+            None if self.track_hits => self.table[index].push_stamped(pair, 0),
+            None => self.table[index].push(pair),
+        }
+        self.size += 1;
+    }
+
+    // moves every pair into a fresh table of exactly `capacity` buckets
+    fn rehash(&mut self, capacity: usize) {
+        let old_capacity = self.table.len();
+        self.relink(capacity);
+        self.resized(old_capacity);
+    }
+
+    // rehash without notifying the resize hook
+    fn relink(&mut self, capacity: usize) {
+        let old_table = mem::replace(
+            &mut self.table,
+            (0..capacity).map(|_| LinkedList::new()).collect(),
+        );
+        self.capacity = capacity;
+        self.threshold = self.threshold_for(capacity);
+        self.churn = 0;
+        for mut list in old_table {
+            while let Some(node) = list.pop_node() {
+                let index = self
+                    .index_for(&node.element().0)
+                    .expect("pairs can't be rehashed into zero buckets");
+                self.table[index].push_node(node);
+            }
+        }
+    }
+
+    // fires the resize hook, unless the amount of buckets stayed the same
+    fn resized(&mut self, old_capacity: usize) {
+        let new_capacity = self.table.len();
+        if old_capacity == new_capacity {
+            return;
+        }
+        if let Some(hook) = self.resize_hook.as_mut() {
+            hook(old_capacity, new_capacity);
+        }
+    }
+
+    fn resize(&mut self) {
+        match self.table.len() {
+            // default resizing
+            0 => {
+                self.table = (0..DEFAULT_CAPACITY).map(|_| LinkedList::new()).collect();
+                self.resized(0);
+            }
+            // when this resize method is called after the threshold is reached
+            // move the nodes themselves, so insertion stamps survive the resize
+            n => self.rehash(n * 2),
+        }
+    }
+}
+
+impl<K: Debug, V: Debug, S> Debug for HashMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HashMap")
+            .field("table", &self.table)
+            .field("size", &self.size)
+            .field("capacity", &self.capacity)
+            .field("threshold", &self.threshold)
+            .field("load_factor", &self.load_factor)
+            .finish_non_exhaustive()
+    }
+}
+
+// the resize hook can't be cloned, the copy starts without one
+impl<K: Clone, V: Clone, S: Clone> Clone for HashMap<K, V, S> {
+    fn clone(&self) -> Self {
+        Self {
+            table: self.table.clone(),
+            size: self.size,
+            load_factor: self.load_factor,
+            capacity: self.capacity,
+            threshold: self.threshold,
+            churn: self.churn,
+            next_stamp: self.next_stamp,
+            hasher: self.hasher.clone(),
+            resize_hook: None,
+            track_hits: self.track_hits,
+        }
+    }
+}
+
+// same pairs means equal, no matter the bucket order, the hashers or the table sizes;
+// looks up through `find`, so comparing doesn't count as reads for `hot_keys`
+impl<K, V, S> PartialEq for HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.size == other.size
+            && self
+                .iter()
+                .all(|(k, v)| other.find(k).is_some_and(|(_k, other_v)| other_v == v))
+    }
+}
+
+impl<K, V, S> Eq for HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    V: Eq,
+    S: BuildHasher,
+{
+}
+
+impl<K, V, S> Index<&K> for HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    fn index(&self, key: &K) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<K, V, S> IndexMut<&K> for HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    fn index_mut(&mut self, key: &K) -> &mut V {
+        self.get_mut(key).expect("no entry found for key")
+    }
+}
+
+// later pairs overwrite earlier ones with the same key; the lower size hint sizes
+// the table up front, so the known part of the input doesn't trigger resizes
+impl<K, V> FromIterator<(K, V)> for HashMap<K, V>
+where
+    K: Hash + Eq,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut map = Self::with_capacity(iter.size_hint().0);
+        for (k, v) in iter {
+            map.put(k, v);
+        }
+        map
+    }
+}
+
+// puts every pair, later pairs overwrite present keys and earlier ones;
+// the lower size hint is reserved up front
+impl<K, V, S> Extend<(K, V)> for HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        // the hint may count keys already present, that only reserves a bit too much
+        self.reserve(iter.size_hint().0);
+        for (k, v) in iter {
+            self.put(k, v);
+        }
+    }
+}
+
+impl<K, V, const N: usize> From<[(K, V); N]> for HashMap<K, V>
+where
+    K: Hash + Eq,
+{
+    fn from(pairs: [(K, V); N]) -> Self {
+        pairs.into_iter().collect()
+    }
+}
+
+impl<K, V, S> Default for HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+pub enum Entry<'a, K, V, S> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+pub struct OccupiedEntry<'a, K, V, S> {
+    map: &'a mut HashMap<K, V, S>,
+    index: usize,
+    key: K,
+}
+
+pub struct VacantEntry<'a, K, V, S> {
+    map: &'a mut HashMap<K, V, S>,
+    key: K,
+}
+
+impl<'a, K, V, S> Entry<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, f: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(f()),
+        }
+    }
+
+    // like or_insert_with, the bool tells whether `f` ran (the entry was vacant)
+    pub fn or_insert_with_audit<F: FnOnce() -> V>(self, f: F) -> (&'a mut V, bool) {
+        match self {
+            Entry::Occupied(entry) => (entry.into_mut(), false),
+            Entry::Vacant(entry) => (entry.insert(f()), true),
+        }
+    }
+
+    // Ok with the freshly inserted value for a vacant entry,
+    // Err with the untouched existing value for an occupied one
+    pub fn insert_if_vacant_else_get(self, value: V) -> Result<&'a mut V, &'a mut V> {
+        match self {
+            Entry::Occupied(entry) => Err(entry.into_mut()),
+            Entry::Vacant(entry) => Ok(entry.insert(value)),
+        }
+    }
+
+    // stores the entry's key together with `value`, returning the previous pair
+    // when the entry was occupied
+    pub fn replace_entry(self, value: V) -> Option<(K, V)> {
+        match self {
+            Entry::Occupied(entry) => Some(entry.replace_entry(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            }
+        }
+    }
+}
+
+impl<'a, K, V, S> OccupiedEntry<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn into_mut(self) -> &'a mut V {
+        let OccupiedEntry { map, index, key } = self;
+        map.table[index]
+            .iter_mut()
+            .find(|(k, _v)| *k == key)
+            .map(|(_k, v)| v)
+            .unwrap()
+    }
+
+    pub fn replace_entry(self, value: V) -> (K, V) {
+        let OccupiedEntry { map, index, key } = self;
+        let pair = map.table[index]
+            .iter_mut()
+            .find(|(k, _v)| *k == key)
+            .unwrap();
+        mem::replace(pair, (key, value))
+    }
+
+    // moves the value under `new_key`, which may live in another bucket;
+    // returns the value `new_key` held before, if any
+    pub fn move_to(self, new_key: K) -> Option<V> {
+        let OccupiedEntry { map, index, key } = self;
+        let (_k, value) = map.table[index].remove_first(|(k, _v)| *k == key).unwrap();
+        map.removed(1);
+        map.put(new_key, value)
+    }
+}
+
+impl<'a, K, V, S> VacantEntry<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn insert(self, value: V) -> &'a mut V {
+        let VacantEntry { map, key } = self;
+        // resize before looking for the bucket, so the index and the returned
+        // reference both belong to the final table
+        map.grow_if_needed();
+        let index = map
+            .index_for(&key)
+            .expect("grow_if_needed allocates the table");
+        map.push_new(index, (key, value));
+        &mut map.table[index].peek_mut().unwrap().1
+    }
+}
+
+// returned by `bounded_insert` when the bucket of a new key is already full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainTooLong {
+    pub bucket: usize,
+    pub max_chain: usize,
+}
+
+impl fmt::Display for ChainTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bucket {} already holds {} pairs",
+            self.bucket, self.max_chain
+        )
+    }
+}
+
+impl std::error::Error for ChainTooLong {}
+
+pub struct Iter<'a, K, V> {
+    index: usize,
+    table: &'a Vec<LinkedList<(K, V)>>,
+    iter: Option<IterLL<'a, (K, V)>>,
+}
+// try_fold/try_for_each are the default ones built on next(), they already stop
+// on the first break and crossing into the next bucket is handled by next() itself;
+// overriding them would need the unstable Try trait
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = &'a (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        // have to check if there is some iter
+        // because the method iter() could be called on the empty map
+        if let Some(iter) = &mut self.iter {
+            // check whether this iter has a next node or not
+            match iter.next() {
+                // if so, return the reference on its contain
+                Some(pair) => Some(pair),
+                // if not, search a next list in the hash_map's table
+                None => loop {
+                    // break this loop on the end of Vec (table)
+                    if self.index == self.table.len() {
+                        break None;
+                    }
+                    match &self.table[self.index].size() {
+                        // if the list size is 0 that means that it has no any next node
+                        // just increment index of table
+                        0 => {
+                            self.index += 1;
+                        }
+                        _ => {
+                            // if there is some size replace self.iter to its iter next value
+                            // and return the reference to its contained value
+                            self.iter = Some(self.table[self.index].iter());
+                            self.index += 1;
+                            break self.iter.as_mut().unwrap().next();
+                        }
+                    }
+                },
+            }
+        } else {
+            None
+        }
+    }
+}
+
+pub struct Keys<'a, K, V>(Iter<'a, K, V>);
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(k, _v)| k)
+    }
+}
+
+pub struct Values<'a, K, V>(Iter<'a, K, V>);
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(_k, v)| v)
+    }
+}
+
+// same walk as `IntoIter`, the buckets are handed out one at a time by the slice's
+// iter_mut, so every list is borrowed mutably only once
+pub struct ValuesMut<'a, K, V> {
+    table: std::slice::IterMut<'a, LinkedList<(K, V)>>,
+    iter: Option<IterMutLL<'a, (K, V)>>,
+}
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((_k, v)) = self.iter.as_mut().and_then(|iter| iter.next()) {
+                return Some(v);
+            }
+            // the current list is exhausted (or there was none yet), move to the next bucket
+            self.iter = Some(self.table.next()?.iter_mut());
+        }
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    type Item = &'a (K, V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, V, S> IntoIterator for HashMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            table: self.table.into_iter(),
+            iter: None,
+        }
+    }
+}
+
+// owns the buckets not reached yet and drains them one list at a time
+pub struct IntoIter<K, V> {
+    table: std::vec::IntoIter<LinkedList<(K, V)>>,
+    iter: Option<IntoIterLL<(K, V)>>,
+}
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(pair) = self.iter.as_mut().and_then(|iter| iter.next()) {
+                return Some(pair);
+            }
+            // the current list is drained (or there was none yet), move to the next bucket
+            self.iter = Some(self.table.next()?.into_iter());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hash, Hasher};
+
+    use crate::DEFAULT_CAPACITY;
+
+    use super::{ChainTooLong, Entry, FnvHasher, HashMap};
+
+    // every key hashes the same, so all of them share one bucket
+    #[derive(Debug, PartialEq, Eq)]
+    struct Collide(i32);
+
+    impl Hash for Collide {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            0.hash(state);
+        }
+    }
+
+    // keys landing in the same bucket as `0` with the current table size
+    fn colliding_keys<V, S: BuildHasher>(map: &HashMap<i32, V, S>, n: usize) -> Vec<i32> {
+        let target = map.index_for(&0).unwrap();
+        (0..)
+            .filter(|k| map.index_for(k) == Some(target))
+            .take(n)
+            .collect()
+    }
+
+    #[test]
+    fn basic() {
+        let mut map: HashMap<i32, i32> = HashMap::new();
+        map.put(1, 555);
+        assert_eq!(map.size(), 1);
+        //default capacity
+        assert_eq!(map.table.len(), DEFAULT_CAPACITY);
+    }
+
+    #[test]
+    fn put() {
+        let mut map: HashMap<String, String> = HashMap::new();
+        assert_eq!(map.size(), 0);
+
+        map.put("key_1".to_string(), "value_1".to_string());
+        map.put("key_2".to_string(), "value_2".to_string());
+        assert_eq!(map.size(), 2);
+
+        let old_value = map.put("key_1".to_string(), "value_2".to_string());
+        assert_eq!(old_value, Some("value_1".to_string()));
+        assert_eq!(map.size(), 2);
+    }
+
+    #[test]
+    fn get() {
+        let mut map = HashMap::new();
+
+        map.put("key_1".to_string(), "value_1".to_string());
+        assert_eq!(map.size(), 1);
+
+        // reading doesn't need a mutable map
+        let map = map;
+        let v = map.get(&"key_1".to_string());
+        let n = map.get(&"empty".to_string());
+        assert_eq!(v, Some(&"value_1".to_string()));
+        assert_eq!(n, None);
+        assert_eq!(map.size(), 1);
+    }
+
+    #[test]
+    fn resize() {
+        let mut map = HashMap::new();
+        map.put(1, 1);
+        assert_eq!(map.size(), 1);
+        assert_eq!(map.table.len(), DEFAULT_CAPACITY);
+        map.remove(&1);
+        assert_eq!(map.size(), 0);
+        assert_eq!(map.table.len(), DEFAULT_CAPACITY);
+        for i in 0..7 {
+            map.put(i, i);
+        }
+        assert_eq!(map.size(), 7);
+        assert_eq!(map.table.len(), DEFAULT_CAPACITY * 2);
+        for i in 7..16 {
+            map.put(i, i);
+        }
+        assert_eq!(map.size(), 16);
+        assert_eq!(map.table.len(), DEFAULT_CAPACITY * 4);
+    }
+
+    #[test]
+    fn iter() {
+        let mut map = HashMap::new();
+
+        let mut pairs_count = 0;
+
+        for _pair in map.iter() {
+            pairs_count += 1;
+        }
+
+        assert_eq!(pairs_count, 0);
+
+        map.put("a", 17);
+        map.put("b", 78);
+        map.put("c", 777);
+
+        for (k, v) in map.iter() {
+            match *k {
+                "a" => assert_eq!(*v, 17),
+                "b" => assert_eq!(*v, 78),
+                "c" => assert_eq!(*v, 777),
+                _ => unreachable!(),
+            }
+            pairs_count += 1;
+        }
+
+        assert_eq!(pairs_count, 3);
+    }
+
+    #[test]
+    fn entry_or_insert_with_across_resize() {
+        let mut map = HashMap::new();
+        // fill the map exactly to the threshold
+        for i in 0..6 {
+            map.put(i, i);
+        }
+        assert_eq!(map.size(), map.threshold);
+        assert_eq!(map.table.len(), DEFAULT_CAPACITY);
+
+        // this insert has to resize the table before choosing a bucket
+        *map.entry(6).or_insert_with(|| 0) += 60;
+
+        assert_eq!(map.table.len(), DEFAULT_CAPACITY * 2);
+        assert_eq!(map.size(), 7);
+        assert_eq!(map.get(&6), Some(&60));
+        for i in 0..6 {
+            assert_eq!(map.get(&i), Some(&i));
+        }
+    }
+
+    #[test]
+    fn count_distinct_hashes() {
+        let mut map = HashMap::new();
+        map.put(0, 0);
+        for k in colliding_keys(&map, 3) {
+            map.put(k, k);
+        }
+        let used_buckets = map.table.iter().filter(|list| list.size() > 0).count();
+
+        assert_eq!(used_buckets, 1);
+        assert_eq!(map.count_distinct_hashes(), 3);
+        assert!(map.count_distinct_hashes() > used_buckets);
+    }
+
+    #[test]
+    fn reinsert_reversed() {
+        let mut map = HashMap::new();
+        map.put(0, 0);
+        let keys = colliding_keys(&map, 3);
+        for k in &keys[1..] {
+            map.put(*k, *k);
+        }
+        let index = map.index_for(&0).unwrap();
+        let order = |map: &HashMap<i32, i32>| -> Vec<i32> {
+            map.table[index].iter().map(|(k, _v)| *k).collect()
+        };
+        assert_eq!(order(&map), vec![keys[2], keys[1], keys[0]]);
+
+        map.reinsert_reversed();
+
+        assert_eq!(order(&map), keys);
+        assert_eq!(map.size(), 3);
+    }
+
+    #[test]
+    fn get_cloned() {
+        let mut map = HashMap::new();
+        assert_eq!(map.get_cloned(&"key_1".to_string()), None);
+
+        map.put("key_1".to_string(), "value_1".to_string());
+
+        let first = map.get_cloned(&"key_1".to_string());
+        let second = map.get_cloned(&"key_1".to_string());
+        assert_eq!(first, Some("value_1".to_string()));
+        assert_eq!(first, second);
+        assert_eq!(map.size(), 1);
+    }
+
+    #[test]
+    fn transfer() {
+        let mut map = HashMap::new();
+        map.put("alice", 100);
+        map.put("bob", 50);
+
+        let moved = map.transfer(&"alice", &"bob", |from, to| {
+            *from -= 30;
+            *to += 30;
+        });
+
+        assert!(moved);
+        assert_eq!(map.get_cloned(&"alice"), Some(70));
+        assert_eq!(map.get_cloned(&"bob"), Some(80));
+        assert_eq!(map.iter().map(|(_k, v)| v).sum::<i32>(), 150);
+        assert!(!map.transfer(&"alice", &"carol", |_from, _to| unreachable!()));
+    }
+
+    #[test]
+    fn transfer_same_key() {
+        let mut map = HashMap::new();
+        map.put("alice", 100);
+
+        assert!(!map.transfer(&"alice", &"alice", |_from, _to| unreachable!()));
+        assert_eq!(map.get_cloned(&"alice"), Some(100));
+    }
+
+    #[test]
+    fn most_common() {
+        let mut map = HashMap::new();
+        assert_eq!(map.most_common(), None);
+
+        map.put("apple", 3);
+        map.put("pear", 7);
+        map.put("plum", 1);
+        map.put("fig", 5);
+
+        assert_eq!(map.most_common(), Some((&"pear", &7)));
+    }
+
+    #[test]
+    fn iter_buckets_mut() {
+        let mut map = HashMap::new();
+        for i in 0..20 {
+            map.put(i, i * 10);
+        }
+
+        map.iter_buckets_mut().for_each(|list| list.reverse());
+
+        assert_eq!(map.size(), 20);
+        assert_eq!(map.iter().count(), 20);
+        for i in 0..20 {
+            assert_eq!(map.get_cloned(&i), Some(i * 10));
+        }
+    }
+
+    #[test]
+    fn capacity_utilization_report() {
+        let mut map = HashMap::new();
+        for i in 0..5 {
+            map.put(i, i);
+        }
+
+        let report = map.capacity_utilization_report();
+
+        assert!(report.contains("size: 5"));
+        assert!(report.contains(&format!("capacity: {}", DEFAULT_CAPACITY)));
+        assert!(report.contains(&format!("longest chain: {}", map.longest_chain())));
+    }
+
+    #[test]
+    fn load_entries_from_slice() {
+        let mut map = HashMap::new();
+        let entries = [
+            ("host", "localhost"),
+            ("port", "8080"),
+            ("host", "example.com"),
+        ];
+
+        map.load_entries_from_slice(&entries);
+        map.load_entries_from_slice(&entries);
+
+        assert_eq!(map.size(), 2);
+        assert_eq!(map.get_cloned(&"host"), Some("example.com"));
+        assert_eq!(map.get_cloned(&"port"), Some("8080"));
+    }
+
+    #[test]
+    fn entry_replace_entry() {
+        let mut map = HashMap::new();
+        map.put("key".to_string(), 1);
+        let fresh_key = "key".to_string();
+        let fresh_ptr = fresh_key.as_ptr();
+
+        let old = map.entry(fresh_key).replace_entry(2);
+
+        assert_eq!(old, Some(("key".to_string(), 1)));
+        let (stored_key, stored_value) = map.iter().next().unwrap();
+        assert_eq!(stored_key.as_ptr(), fresh_ptr);
+        assert_eq!(*stored_value, 2);
+        assert_eq!(map.size(), 1);
+
+        assert_eq!(map.entry("other".to_string()).replace_entry(3), None);
+        assert_eq!(map.get_cloned(&"other".to_string()), Some(3));
+    }
+
+    #[test]
+    fn should_compact() {
+        let mut map = HashMap::new();
+        for i in 0..64 {
+            map.put(i, i);
+        }
+        assert!(!map.should_compact());
+
+        for i in 0..60 {
+            map.remove(&i);
+        }
+
+        assert_eq!(map.size(), 4);
+        assert!(map.should_compact());
+
+        map.compact();
+
+        assert_eq!(map.table.len(), DEFAULT_CAPACITY);
+        assert!(!map.should_compact());
+        assert_eq!(map.size(), 4);
+        for i in 60..64 {
+            assert_eq!(map.get(&i), Some(&i));
+        }
+    }
+
+    #[test]
+    fn iter_try_fold() {
+        use std::ops::ControlFlow;
+
+        let mut map = HashMap::new();
+        for i in 0..20 {
+            map.put(i, i);
+        }
+        let used_buckets = map.table.iter().filter(|list| list.size() > 0).count();
+        assert!(used_buckets > 1);
+        // the target is the first pair of the last non-empty bucket
+        let target = map
+            .table
+            .iter()
+            .rev()
+            .find_map(|list| list.peek())
+            .unwrap()
+            .0;
+
+        let mut visited = 0;
+        let found = map.iter().try_for_each(|(k, _v)| {
+            visited += 1;
+            if *k == target {
+                ControlFlow::Break(*k)
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+
+        assert_eq!(found, ControlFlow::Break(target));
+        assert_eq!(
+            visited,
+            map.iter().position(|(k, _v)| *k == target).unwrap() + 1
+        );
+
+        let mut iter = map.iter();
+        let sum = iter.try_fold(
+            0,
+            |acc, (k, _v)| if *k == target { None } else { Some(acc + k) },
+        );
+        assert_eq!(sum, None);
+        assert_eq!(iter.count(), 20 - visited);
+    }
+
+    #[test]
+    fn optimize_capacity() {
+        let mut map = HashMap::new();
+        map.put(0, 0);
+        let keys = colliding_keys(&map, 4);
+        for k in &keys {
+            map.put(*k, *k);
+        }
+        assert_eq!(map.longest_chain(), 4);
+        let candidates = [7, 11, 13];
+
+        let chosen = map.optimize_capacity(&candidates);
+
+        assert!(candidates.contains(&chosen));
+        assert_eq!(map.table.len(), chosen);
+        assert!(map.longest_chain() < 4);
+        assert_eq!(map.size(), 4);
+        for k in &keys {
+            assert_eq!(map.get_cloned(k), Some(*k));
+        }
+    }
+
+    #[test]
+    fn optimize_capacity_survives_put() {
+        let mut map = HashMap::new();
+        for i in 0..20 {
+            map.put(i, i);
+        }
+        assert_eq!(map.table.len(), 32);
+
+        // none of these hold 20 pairs under the 0.75 load factor
+        assert_eq!(map.optimize_capacity(&[13, 17, 19]), 32);
+        assert_eq!(map.optimize_capacity(&[13, 17, 19, 29]), 29);
+
+        map.put(20, 20);
+        assert_eq!(map.table.len(), 29);
+        for i in 0..21 {
+            assert_eq!(map.get_cloned(&i), Some(i));
+        }
+    }
+
+    #[test]
+    fn bump() {
+        let mut map = HashMap::new();
+
+        map.bump("hits", 2);
+        map.bump("hits", 3);
+        map.bump("hits", 5);
+        map.bump("misses", 1);
+
+        assert_eq!(map.get_cloned(&"hits"), Some(10));
+        assert_eq!(map.get_cloned(&"misses"), Some(1));
+        assert_eq!(map.size(), 2);
+    }
+
+    #[test]
+    fn sorted_by_value() {
+        let mut map = HashMap::new();
+        map.put("b", 3);
+        map.put("a", 10);
+        map.put("d", 1);
+        map.put("c", 7);
+
+        let ranked = map.sorted_by_value();
+
+        assert_eq!(
+            ranked,
+            vec![(&"a", &10), (&"c", &7), (&"b", &3), (&"d", &1)]
+        );
+        assert!(ranked.windows(2).all(|w| w[0].1 >= w[1].1));
+    }
+
+    #[test]
+    fn retain_top_n() {
+        let mut map = HashMap::new();
+        for i in 0..10 {
+            map.put(format!("player_{}", i), i * 10);
+        }
+
+        map.retain_top_n(3);
+
+        assert_eq!(map.size(), 3);
+        assert_eq!(map.iter().count(), 3);
+        for i in 7..10 {
+            assert_eq!(map.get_cloned(&format!("player_{}", i)), Some(i * 10));
+        }
+        assert_eq!(map.get_cloned(&"player_6".to_string()), None);
+    }
+
+    #[test]
+    fn insert_tracked() {
+        let mut map = HashMap::new();
+        let (old, _stored) = map.insert_tracked("key", 1);
+        assert_eq!(old, None);
+
+        let (old, stored) = map.insert_tracked("key", 2);
+        assert_eq!(old, Some(1));
+        assert_eq!(*stored, 2);
+        *stored += 40;
+
+        assert_eq!(map.get_cloned(&"key"), Some(42));
+        assert_eq!(map.size(), 1);
+    }
+
+    #[test]
+    fn range() {
+        let mut map = HashMap::new();
+        for i in 0..20 {
+            map.put(i, i * 2);
+        }
+
+        let keys: Vec<i32> = map.range(&5, &10).iter().map(|(k, _v)| **k).collect();
+
+        assert_eq!(keys, (5..=10).collect::<Vec<_>>());
+        assert_eq!(map.range(&8, &8), vec![(&8, &16)]);
+        assert!(map.range(&30, &40).is_empty());
+    }
+
+    #[test]
+    fn get_pair_mut_same_bucket() {
+        let mut map = HashMap::new();
+        map.put(Collide(1), 10);
+        map.put(Collide(2), 20);
+        map.put(Collide(3), 30);
+        assert_eq!(map.longest_chain(), 3);
+
+        let (a, b) = map
+            .get_pair_mut_same_bucket(&Collide(1), &Collide(3))
+            .unwrap();
+        *a += 1;
+        *b += 3;
+
+        assert_eq!(map.get_cloned(&Collide(1)), Some(11));
+        assert_eq!(map.get_cloned(&Collide(2)), Some(20));
+        assert_eq!(map.get_cloned(&Collide(3)), Some(33));
+        assert!(map
+            .get_pair_mut_same_bucket(&Collide(1), &Collide(4))
+            .is_none());
+        assert!(map
+            .get_pair_mut_same_bucket(&Collide(2), &Collide(2))
+            .is_none());
+    }
+
+    #[test]
+    fn iter_by_insertion_order() {
+        let mut map = HashMap::with_insertion_order();
+        let words = [
+            "one", "two", "three", "four", "five", "six", "seven", "eight",
+        ];
+        // enough pairs to resize the table at least once
+        for (i, word) in words.iter().enumerate() {
+            map.put(*word, i);
+        }
+        map.put("two", 22);
+        map.remove(&"four");
+        map.entry("nine").or_insert(8);
+        assert!(map.table.len() > DEFAULT_CAPACITY);
+
+        let ordered: Vec<&str> = map
+            .iter_by_insertion_order()
+            .iter()
+            .map(|(k, _v)| **k)
+            .collect();
+
+        assert_eq!(
+            ordered,
+            vec!["one", "two", "three", "five", "six", "seven", "eight", "nine"]
+        );
+        assert_eq!(map.iter_by_insertion_order()[1], (&"two", &22));
+    }
+
+    #[test]
+    fn entry_move_to() {
+        let mut map = HashMap::new();
+        for i in 0..5 {
+            map.put(i, i * 100);
+        }
+
+        let Entry::Occupied(entry) = map.entry(2) else {
+            panic!("key 2 is in the map");
+        };
+        assert_eq!(entry.move_to(42), None);
+
+        assert_eq!(map.get_cloned(&2), None);
+        assert_eq!(map.get_cloned(&42), Some(200));
+        assert_eq!(map.size(), 5);
+    }
+
+    #[test]
+    fn bucket_load_variance() {
+        let mut good = HashMap::new();
+        let mut bad = HashMap::new();
+        assert_eq!(good.bucket_load_variance(), 0.0);
+        for i in 0..6 {
+            good.put(i, i);
+            bad.put(Collide(i), i);
+        }
+
+        // all six pairs in one of eight buckets
+        assert_eq!(bad.bucket_load_variance(), 3.9375);
+        assert!(good.bucket_load_variance() < bad.bucket_load_variance());
+    }
+
+    #[test]
+    fn snapshot_iter() {
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+
+        let map = Arc::new(Mutex::new(HashMap::new()));
+        for i in 0..16 {
+            map.lock().unwrap().put(i, 0);
+        }
+
+        // every write sets all values to the same generation under one lock
+        let writer = {
+            let map = Arc::clone(&map);
+            thread::spawn(move || {
+                for generation in 1..=200 {
+                    let mut map = map.lock().unwrap();
+                    for i in 0..16 {
+                        map.put(i, generation);
+                    }
+                }
+            })
+        };
+
+        for _ in 0..200 {
+            let snapshot = map.lock().unwrap().snapshot_iter();
+            let values: Vec<i32> = snapshot.map(|(_k, v)| v).collect();
+            assert_eq!(values.len(), 16);
+            assert!(values.iter().all(|v| *v == values[0]));
+        }
+
+        writer.join().unwrap();
+        assert!(map.lock().unwrap().snapshot_iter().all(|(_k, v)| v == 200));
+    }
+
+    #[test]
+    fn entry_count_in_range_of_buckets() {
+        let mut map = HashMap::new();
+        for i in 0..12 {
+            map.put(i, i);
+        }
+        let manual: usize = (2..7).map(|i| map.table[i].size()).sum();
+
+        assert_eq!(map.entry_count_in_range_of_buckets(2, 7), manual);
+        assert_eq!(map.entry_count_in_range_of_buckets(0, 1000), 12);
+        assert_eq!(map.entry_count_in_range_of_buckets(7, 2), 0);
+        assert_eq!(map.entry_count_in_range_of_buckets(500, 1000), 0);
+    }
+
+    #[test]
+    fn take_min_value_entry() {
+        let mut map = HashMap::new();
+        for (name, score) in [("d", 40), ("a", 10), ("e", 50), ("c", 30), ("b", 20)] {
+            map.put(name, score);
+        }
+
+        let mut taken = Vec::new();
+        while let Some(pair) = map.take_min_value_entry() {
+            taken.push(pair);
+        }
+
+        assert_eq!(
+            taken,
+            vec![("a", 10), ("b", 20), ("c", 30), ("d", 40), ("e", 50)]
+        );
+        assert_eq!(map.size(), 0);
+    }
+
+    #[test]
+    fn grow_until() {
+        let mut map = HashMap::new();
+        map.put(0, 0);
+        for k in colliding_keys(&map, 5) {
+            map.put(k, k);
+        }
+        assert_eq!(map.longest_chain(), 5);
+
+        map.grow_until(|map| map.longest_chain() <= 2);
+
+        assert!(map.longest_chain() <= 2);
+        assert!(map.table.len() > DEFAULT_CAPACITY);
+        assert_eq!(map.size(), 5);
+
+        let mut empty: HashMap<i32, i32> = HashMap::new();
+        empty.grow_until(|map| map.table.len() >= 32);
+        assert_eq!(empty.table.len(), 32);
+    }
+
+    #[test]
+    fn each_bucket_stats() {
+        let mut map = HashMap::new();
+        assert!(map.each_bucket_stats().is_empty());
+        for i in 0..3 {
+            map.put(Collide(i), i);
+        }
+
+        let index = map.index_for(&Collide(0)).unwrap();
+
+        assert_eq!(map.each_bucket_stats(), vec![(index, 3)]);
+    }
+
+    #[test]
+    fn consume_into_pairs_sorted() {
+        let mut map = HashMap::new();
+        for k in [7, 3, 9, 1, 5, 8, 2] {
+            map.put(k, k.to_string());
+        }
+
+        let pairs = map.consume_into_pairs_sorted();
+
+        let expected: Vec<(i32, String)> = [1, 2, 3, 5, 7, 8, 9]
+            .iter()
+            .map(|k| (*k, k.to_string()))
+            .collect();
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn replace_table_with_capacity() {
+        let mut map = HashMap::new();
+        for i in 0..10 {
+            map.put(i, i * 3);
+        }
+
+        map.replace_table_with_capacity(13);
+
+        assert_eq!(map.table.len(), 13);
+        assert_eq!(map.capacity, 13);
+        assert_eq!(map.threshold, 9);
+        assert_eq!(map.size(), 10);
+        for i in 0..10 {
+            assert_eq!(map.get_cloned(&i), Some(i * 3));
+            assert_eq!(
+                map.table[map.index_for(&i).unwrap()]
+                    .iter()
+                    .filter(|(k, _v)| *k == i)
+                    .count(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn key_exists_in_bucket_only() {
+        let mut map = HashMap::new();
+        map.put("key", 1);
+        let index = map.index_for(&"key").unwrap();
+        let neighbour = (index + 1) % map.table.len();
+
+        assert!(map.key_exists_in_bucket_only(&"key", index));
+        assert!(!map.key_exists_in_bucket_only(&"key", neighbour));
+        assert!(!map.key_exists_in_bucket_only(&"key", map.table.len()));
+    }
+
+    #[test]
+    fn defragment() {
+        let mut map = HashMap::new();
+        for round in 0..10 {
+            for i in 0..100 {
+                map.put(i, i + round);
+            }
+            for i in (0..100).filter(|i| i % 3 == round % 3) {
+                map.remove(&i);
+            }
+        }
+        let before: Vec<Vec<(i32, i32)>> = map
+            .table
+            .iter()
+            .map(|list| list.iter().cloned().collect())
+            .collect();
+        let size = map.size();
+
+        map.defragment();
+
+        let after: Vec<Vec<(i32, i32)>> = map
+            .table
+            .iter()
+            .map(|list| list.iter().cloned().collect())
+            .collect();
+        assert_eq!(before, after);
+        assert_eq!(map.size(), size);
+        for (k, v) in before.iter().flatten() {
+            assert_eq!(map.get_cloned(k), Some(*v));
+        }
+    }
+
+    #[test]
+    fn with_fnv_hasher() {
+        let mut map = HashMap::with_fnv_hasher();
+        for i in 0..100 {
+            map.put(format!("key_{}", i), i);
+        }
+
+        assert_eq!(map.size(), 100);
+        for i in 0..100 {
+            assert_eq!(map.get_cloned(&format!("key_{}", i)), Some(i));
+        }
+        assert_eq!(map.get_cloned(&"missing".to_string()), None);
+
+        let mut hasher = FnvHasher::default();
+        "key_7".to_string().hash(&mut hasher);
+        assert_eq!(map.hash_of(&"key_7".to_string()), hasher.finish());
+    }
+
+    #[test]
+    fn entry_or_insert_reference_after_resize() {
+        let mut map = HashMap::new();
+        for i in 0..6 {
+            map.put(i, i);
+        }
+        assert_eq!(map.size(), map.threshold);
+
+        let value = map.entry(6).or_insert(0);
+        *value = 600;
+
+        assert_eq!(map.table.len(), DEFAULT_CAPACITY * 2);
+        assert_eq!(map.get(&6), Some(&600));
+    }
+
+    #[test]
+    fn entry_word_count() {
+        // enough distinct words that the vacant inserts cross several thresholds
+        let text: Vec<String> = (0..40)
+            .chain(0..10)
+            .chain(0..3)
+            .map(|i| i.to_string())
+            .collect();
+        let mut map = HashMap::new();
+
+        for word in &text {
+            *map.entry(word.as_str()).or_insert(0) += 1;
+        }
+
+        assert_eq!(map.size(), 40);
+        assert_eq!(map.get(&"0"), Some(&3));
+        assert_eq!(map.get(&"5"), Some(&2));
+        assert_eq!(map.get(&"39"), Some(&1));
+        assert_eq!(map.iter().map(|(_w, n)| n).sum::<i32>(), 53);
+    }
+
+    #[test]
+    fn iter_chunks_by_bucket() {
+        let mut map = HashMap::new();
+        for i in 0..30 {
+            map.put(i, i + 1);
+        }
+        let used_buckets = map.table.iter().filter(|list| list.size() > 0).count();
+
+        let chunks: Vec<Vec<(&i32, &i32)>> = map.iter_chunks_by_bucket().collect();
+
+        assert_eq!(chunks.len(), used_buckets);
+        assert!(chunks.iter().all(|chunk| !chunk.is_empty()));
+        let mut flattened: Vec<(i32, i32)> = chunks
+            .into_iter()
+            .flatten()
+            .map(|(k, v)| (*k, *v))
+            .collect();
+        flattened.sort();
+        assert_eq!(flattened, (0..30).map(|i| (i, i + 1)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn remove_with_bucket_len() {
+        let mut map = HashMap::new();
+        for i in 0..4 {
+            map.put(Collide(i), i);
+        }
+        assert_eq!(map.longest_chain(), 4);
+
+        assert_eq!(map.remove_with_bucket_len(&Collide(2)), Some((2, 3)));
+        assert_eq!(map.remove_with_bucket_len(&Collide(2)), None);
+        assert_eq!(map.size(), 3);
+        assert_eq!(map.get_cloned(&Collide(2)), None);
+        assert_eq!(map.get_cloned(&Collide(3)), Some(3));
+    }
+
+    #[test]
+    fn entry_insert_if_vacant_else_get() {
+        let mut map = HashMap::new();
+
+        match map.entry("key").insert_if_vacant_else_get(1) {
+            Ok(value) => *value += 10,
+            Err(_) => panic!("the entry was vacant"),
+        }
+        match map.entry("key").insert_if_vacant_else_get(2) {
+            Ok(_) => panic!("the entry was occupied"),
+            Err(value) => assert_eq!(*value, 11),
+        }
+
+        assert_eq!(map.get_cloned(&"key"), Some(11));
+        assert_eq!(map.size(), 1);
+    }
+
+    #[test]
+    fn clone_subset() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.put(i, i * 2);
+        }
+
+        let even = map.clone_subset(|k, _v| k % 2 == 0);
+
+        assert_eq!(even.size(), 50);
+        assert_eq!(even.table.len(), 128);
+        for i in 0..100 {
+            let expected = if i % 2 == 0 { Some(i * 2) } else { None };
+            assert_eq!(even.get_cloned(&i), expected);
+        }
+        assert_eq!(map.size(), 100);
+    }
+
+    #[test]
+    fn hash_of() {
+        let mut map = HashMap::with_fnv_hasher();
+        map.put("key", 1);
+
+        let mut hasher = FnvHasher::default();
+        "key".hash(&mut hasher);
+
+        assert_eq!(map.hash_of(&"key"), map.hash_of(&"key"));
+        assert_eq!(map.hash_of(&"key"), hasher.finish());
+        assert_eq!(
+            map.hash_of(&"key") as usize % map.table.len(),
+            map.index_for(&"key").unwrap()
+        );
+    }
+
+    #[test]
+    fn on_resize() {
+        use std::sync::{Arc, Mutex};
+
+        let mut map = HashMap::new();
+        map.put(0, 0);
+        let resizes = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&resizes);
+        map.on_resize(Box::new(move |old, new| {
+            recorded.lock().unwrap().push((old, new))
+        }));
+
+        for i in 1..13 {
+            map.put(i, i);
+        }
+
+        assert_eq!(map.table.len(), DEFAULT_CAPACITY * 4);
+        assert_eq!(*resizes.lock().unwrap(), vec![(8, 16), (16, 32)]);
+    }
+
+    #[test]
+    fn batch_contains() {
+        let mut map = HashMap::new();
+        assert_eq!(map.batch_contains(&[1, 2]), vec![false, false]);
+        map.put(1, "one");
+        map.put(3, "three");
+
+        assert_eq!(
+            map.batch_contains(&[1, 2, 3, 4, 1]),
+            vec![true, false, true, false, true]
+        );
+    }
+
+    #[test]
+    fn apply() {
+        let mut map = HashMap::new();
+        for i in 0..10 {
+            map.put(i, i * 10);
+        }
+
+        map.apply(|_k, v| *v -= 1);
+
+        for i in 0..10 {
+            assert_eq!(map.get_cloned(&i), Some(i * 10 - 1));
+        }
+        assert_eq!(map.size(), 10);
+    }
+
+    #[test]
+    fn entry_or_insert_with_audit() {
+        let mut map = HashMap::new();
+
+        let (value, created) = map.entry("key").or_insert_with_audit(|| 1);
+        assert_eq!((*value, created), (1, true));
+
+        for _ in 0..2 {
+            let (value, created) = map.entry("key").or_insert_with_audit(|| unreachable!());
+            assert_eq!((*value, created), (1, false));
+        }
+    }
+
+    #[test]
+    fn remove_matching_value() {
+        let mut map = HashMap::new();
+        for i in 0..5 {
+            map.put(i, "shared");
+        }
+        map.put(10, "ten");
+        map.put(11, "eleven");
+
+        assert_eq!(map.remove_matching_value(&"shared"), 5);
+
+        assert_eq!(map.size(), 2);
+        assert_eq!(map.get(&10), Some(&"ten"));
+        assert_eq!(map.get(&11), Some(&"eleven"));
+        assert_eq!(map.get(&0), None);
+        assert_eq!(map.remove_matching_value(&"shared"), 0);
+    }
+
+    #[test]
+    fn remove() {
+        let mut map = HashMap::new();
+        assert_eq!(map.remove(&"key_1".to_string()), None);
+        map.put("key_1".to_string(), 1);
+        map.put("key_2".to_string(), 2);
+        let key = "key_1".to_string();
+
+        assert_eq!(map.remove(&key), Some(1));
+        assert_eq!(map.size(), 1);
+        assert_eq!(map.get(&key), None);
+        // the lookup key is still ours
+        assert_eq!(key, "key_1");
+    }
+
+    #[test]
+    fn remove_absent() {
+        let mut map = HashMap::new();
+        map.put(1, 1);
+
+        assert_eq!(map.remove(&2), None);
+        assert_eq!(map.size(), 1);
+    }
+
+    #[test]
+    fn remove_to_empty() {
+        let mut map = HashMap::new();
+        for i in 0..10 {
+            map.put(i, i);
+        }
+
+        for i in 0..10 {
+            assert_eq!(map.remove(&i), Some(i));
+        }
+
+        assert_eq!(map.size(), 0);
+        assert_eq!(map.iter().count(), 0);
+        assert!(map.table.iter().all(|list| list.size() == 0));
+    }
+
+    #[test]
+    fn remove_twice() {
+        let mut map = HashMap::new();
+        map.put("key", 1);
+
+        assert_eq!(map.remove(&"key"), Some(1));
+        assert_eq!(map.remove(&"key"), None);
+        assert_eq!(map.size(), 0);
+    }
+
+    #[test]
+    fn value_without_debug() {
+        // deliberately doesn't implement Debug
+        struct Opaque(i32);
+
+        let mut map = HashMap::new();
+        map.put("key", Opaque(1));
+        *map.entry("other").or_insert(Opaque(0)) = Opaque(2);
+
+        assert_eq!(map.get(&"key").map(|v| v.0), Some(1));
+        assert_eq!(map.remove(&"other").map(|v| v.0), Some(2));
+    }
+
+    #[test]
+    fn hot_keys() {
+        let mut map = HashMap::new();
+        for i in 0..10 {
+            map.put(i, i);
+        }
+        map.get(&1);
+        assert!(map.hot_keys(3).is_empty());
+
+        map.set_hit_tracking(true);
+        for (key, reads) in [(4, 5), (7, 3), (2, 1)] {
+            for _ in 0..reads {
+                map.get(&key);
+            }
+        }
+
+        map.get_mut(&2);
+        map.get_mut(&2);
+        map.get_mut(&2);
+
+        assert_eq!(map.hot_keys(2), vec![&4, &2]);
+        assert_eq!(map.hot_keys(10), vec![&4, &2, &7]);
+    }
+
+    #[test]
+    fn upsert() {
+        let mut map = HashMap::new();
+
+        map.upsert("tags", vec!["new"], |tags| tags.push("again"));
+        assert_eq!(map.get(&"tags"), Some(&vec!["new"]));
+
+        map.upsert("tags", vec!["new"], |tags| tags.push("again"));
+        assert_eq!(map.get(&"tags"), Some(&vec!["new", "again"]));
+        assert_eq!(map.size(), 1);
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut map = HashMap::new();
+        map.put("k", 1);
+
+        *map.get_mut(&"k").unwrap() += 1;
+
+        assert_eq!(map.get(&"k"), Some(&2));
+        assert_eq!(map.get_mut(&"missing"), None);
+        assert_eq!(HashMap::<&str, i32>::new().get_mut(&"k"), None);
+    }
+
+    #[test]
+    fn filter_collect_to_map() {
+        let mut map = HashMap::new();
+        for (name, age) in [("ann", 31), ("bob", 17), ("cid", 45), ("dan", 12)] {
+            map.put(name, age);
+        }
+
+        let adults = map.filter_collect_to_map(|_name, age| *age >= 18);
+
+        let mut names: Vec<_> = adults.iter().map(|(name, _age)| *name).collect();
+        names.sort();
+        assert_eq!(names, vec!["ann", "cid"]);
+        assert_eq!(adults.get(&"cid"), Some(&45));
+        assert_eq!(map.size(), 4);
+    }
+
+    #[test]
+    fn contains_key() {
+        let mut map = HashMap::new();
+        assert!(!map.contains_key(&1));
+        map.put(1, "one");
+
+        assert!(map.contains_key(&1));
+        assert!(!map.contains_key(&2));
+        assert_eq!(map.size(), 1);
+    }
+
+    #[test]
+    fn contains_key_colliding() {
+        let mut map = HashMap::new();
+        map.put(Collide(1), "one");
+        map.put(Collide(2), "two");
+
+        assert!(map.contains_key(&Collide(1)));
+        assert!(map.contains_key(&Collide(2)));
+        assert!(!map.contains_key(&Collide(3)));
+    }
+
+    #[test]
+    fn estimate_memory_fragmentation() {
+        let mut map = HashMap::new();
+        assert_eq!(map.estimate_memory_fragmentation(), 0.0);
+        for i in 0..100 {
+            map.put(i, i);
+        }
+        let fresh = map.estimate_memory_fragmentation();
+
+        for i in 0..90 {
+            map.remove(&i);
+        }
+        let churned = map.estimate_memory_fragmentation();
+
+        assert!(churned > fresh);
+        assert!((0.0..=1.0).contains(&fresh));
+        assert!((0.0..=1.0).contains(&churned));
+    }
+
+    #[test]
+    fn keys_and_values() {
+        let mut map = HashMap::new();
+        map.put("one", 1);
+        map.put("two", 2);
+        map.put("three", 3);
+
+        let mut keys: Vec<&&str> = map.keys().collect();
+        keys.sort();
+        let mut values: Vec<&i32> = map.values().collect();
+        values.sort();
+
+        assert_eq!(keys, vec![&"one", &"three", &"two"]);
+        assert_eq!(values, vec![&1, &2, &3]);
+        assert_eq!(HashMap::<i32, i32>::new().keys().next(), None);
+    }
+
+    #[test]
+    fn values_mut() {
+        let mut map = HashMap::new();
+        for i in 0..20 {
+            map.put(i, i);
+        }
+
+        for v in map.values_mut() {
+            *v *= 2;
+        }
+
+        for i in 0..20 {
+            assert_eq!(map.get(&i), Some(&(i * 2)));
+        }
+        assert_eq!(HashMap::<i32, i32>::new().values_mut().next(), None);
+    }
+
+    #[test]
+    fn auto_rehash_if_skewed() {
+        let mut map = HashMap::with_hasher(RandomState::new());
+        map.replace_table_with_capacity(64);
+        for k in colliding_keys(&map, 6) {
+            map.put(k, k);
+        }
+        let skewed = map.bucket_load_variance();
+
+        assert!(!map.auto_rehash_if_skewed(skewed));
+        assert!(map.auto_rehash_if_skewed(0.1));
+
+        assert!(map.bucket_load_variance() < skewed);
+        assert_eq!(map.table.len(), 64);
+        assert_eq!(map.size(), 6);
+        for (k, v) in map.iter() {
+            assert_eq!(map.get(k), Some(v));
+        }
+    }
+
+    #[test]
+    fn boxed_closure_values() {
+        let mut map: HashMap<String, Box<dyn Fn() -> i32>> = HashMap::new();
+        let base = 40;
+        map.put("answer".to_string(), Box::new(move || base + 2));
+        map.entry("seven".to_string()).or_insert(Box::new(|| 7));
+        // already there, the boxed closure passed here is dropped unused
+        map.entry("seven".to_string()).or_insert(Box::new(|| 0));
+
+        assert_eq!(map.get(&"answer".to_string()).map(|f| f()), Some(42));
+        assert_eq!(map.get(&"seven".to_string()).map(|f| f()), Some(7));
+        assert!(map.get(&"missing".to_string()).is_none());
+    }
+
+    // hashes every key to 0, the worst case for the chaining
+    struct ConstantState;
+
+    struct ConstantHasher;
+
+    impl Hasher for ConstantHasher {
+        fn write(&mut self, _bytes: &[u8]) {}
+
+        fn finish(&self) -> u64 {
+            0
+        }
+    }
+
+    impl BuildHasher for ConstantState {
+        type Hasher = ConstantHasher;
+
+        fn build_hasher(&self) -> ConstantHasher {
+            ConstantHasher
+        }
+    }
+
+    #[test]
+    fn with_constant_hasher() {
+        let mut map = HashMap::with_hasher(ConstantState);
+        for i in 0..50 {
+            map.put(i, i * 2);
+        }
+
+        assert_eq!(map.longest_chain(), 50);
+        assert_eq!(map.table[0].size(), 50);
+        for i in 0..50 {
+            assert_eq!(map.get(&i), Some(&(i * 2)));
+        }
+        assert_eq!(map.remove(&10), Some(20));
+        assert_eq!(map.get(&10), None);
+        assert_eq!(map.size(), 49);
+    }
+
+    #[test]
+    fn remove_at() {
+        let mut map = HashMap::new();
+        for i in 0..3 {
+            map.put(Collide(i), i);
+        }
+        // push prepends, so the bucket holds 2, 1, 0
+        let bucket = map.index_for(&Collide(0)).unwrap();
+
+        assert_eq!(map.remove_at(bucket, 1), Some((Collide(1), 1)));
+        assert_eq!(map.remove_at(bucket, 2), None);
+        assert_eq!(map.remove_at(map.table.len(), 0), None);
+
+        assert_eq!(map.size(), 2);
+        assert_eq!(map.get(&Collide(0)), Some(&0));
+        assert_eq!(map.get(&Collide(2)), Some(&2));
+        assert_eq!(map.get(&Collide(1)), None);
+    }
+
+    #[test]
+    fn with_capacity() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let mut map = HashMap::with_capacity(1000);
+        let buckets = map.table.len();
+        assert!(buckets.is_power_of_two());
+        assert_eq!(map.capacity, buckets);
+        let resizes = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&resizes);
+        map.on_resize(Box::new(move |_old, _new| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }));
+
+        for i in 0..1000 {
+            map.put(i, i);
+        }
+
+        assert_eq!(map.table.len(), buckets);
+        assert_eq!(resizes.load(Ordering::Relaxed), 0);
+        assert_eq!(
+            HashMap::<i32, i32>::with_capacity(0).table.len(),
+            DEFAULT_CAPACITY
+        );
+    }
+
+    #[test]
+    fn collapse_by() {
+        let mut map = HashMap::new();
+        map.put(("fruit", 1), 3);
+        map.put(("fruit", 2), 4);
+        map.put(("veg", 1), 10);
+        map.put(("fruit", 3), 5);
+
+        let totals = map.collapse_by(|(category, _id)| *category, |a, b| a + b);
+
+        assert_eq!(totals.size(), 2);
+        assert_eq!(totals.get(&"fruit"), Some(&12));
+        assert_eq!(totals.get(&"veg"), Some(&10));
+    }
+
+    #[test]
+    fn with_load_factor() {
+        let mut default = HashMap::new();
+        let mut eager = HashMap::with_load_factor(0.5);
+        for i in 0..6 {
+            default.put(i, i);
+            eager.put(i, i);
+        }
+
+        assert_eq!(default.table.len(), DEFAULT_CAPACITY);
+        assert_eq!(eager.table.len(), DEFAULT_CAPACITY * 2);
+        assert_eq!(eager.threshold, DEFAULT_CAPACITY);
+        for i in 0..6 {
+            assert_eq!(eager.get(&i), Some(&i));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "load factor")]
+    fn with_load_factor_out_of_range() {
+        HashMap::<i32, i32>::with_load_factor(1.5);
+    }
+
+    #[test]
+    fn verify_no_duplicate_keys() {
+        let mut map = HashMap::new();
+        for i in 0..20 {
+            map.put(i, i);
+        }
+        assert!(map.verify_no_duplicate_keys());
+
+        // a copy of the key in a bucket it doesn't hash to
+        let neighbour = (map.index_for(&7).unwrap() + 1) % map.table.len();
+        map.table[neighbour].push((7, 70));
+
+        assert!(!map.verify_no_duplicate_keys());
+
+        // distinct keys sharing one hash are not duplicates
+        let mut colliding = HashMap::new();
+        colliding.put(Collide(1), 1);
+        colliding.put(Collide(2), 2);
+        assert!(colliding.verify_no_duplicate_keys());
+        colliding.table[0].push((Collide(1), 10));
+        assert!(!colliding.verify_no_duplicate_keys());
+    }
+
+    #[test]
+    fn lookups_on_new_map() {
+        let mut map: HashMap<&str, i32> = HashMap::new();
+        assert!(map.table.is_empty());
+
+        assert_eq!(map.index_for(&"key"), None);
+        assert!(!map.contains_key(&"key"));
+        assert_eq!(map.get(&"key"), None);
+        assert_eq!(map.get_mut(&"key"), None);
+        assert_eq!(map.remove(&"key"), None);
+        assert_eq!(map.remove_with_bucket_len(&"key"), None);
+        assert!(map.get_pair_mut_same_bucket(&"a", &"b").is_none());
+        assert!(matches!(map.entry("key"), Entry::Vacant(_)));
+    }
+
+    #[test]
+    fn iter_rev() {
+        let mut map = HashMap::new();
+        for i in 0..20 {
+            map.put(i, i * 2);
+        }
+        // a few chains longer than one, so the order within a chain is checked too
+        for k in colliding_keys(&map, 3) {
+            map.put(k, k);
+        }
+
+        let mut forward: Vec<&(i32, i32)> = map.iter().collect();
+        let backward: Vec<&(i32, i32)> = map.iter_rev().collect();
+        forward.reverse();
+
+        assert_eq!(backward, forward);
+        assert_eq!(HashMap::<i32, i32>::new().iter_rev().count(), 0);
+    }
+
+    #[test]
+    fn clone() {
+        let mut map = HashMap::new();
+        for i in 0..20 {
+            map.put(i, vec![i]);
+        }
+
+        let copy = map.clone();
+        map.get_mut(&3).unwrap().push(30);
+        map.remove(&4);
+        map.put(50, vec![50]);
+
+        assert_eq!(copy.size(), 20);
+        assert_eq!(copy.table.len(), map.table.len());
+        for i in 0..20 {
+            assert_eq!(copy.get(&i), Some(&vec![i]));
+        }
+        assert!(!copy.contains_key(&50));
+    }
+
+    #[test]
+    fn bounded_insert() {
+        let mut map = HashMap::new();
+        for i in 0..3 {
+            assert_eq!(map.bounded_insert(Collide(i), i, 3), Ok(None));
+        }
+        let bucket = map.index_for(&Collide(0)).unwrap();
+
+        assert_eq!(
+            map.bounded_insert(Collide(3), 3, 3),
+            Err(ChainTooLong {
+                bucket,
+                max_chain: 3
+            })
+        );
+        assert_eq!(map.bounded_insert(Collide(1), 10, 3), Ok(Some(1)));
+
+        assert_eq!(map.size(), 3);
+        assert_eq!(map.get(&Collide(1)), Some(&10));
+        assert!(!map.contains_key(&Collide(3)));
+        assert_eq!(
+            ChainTooLong {
+                bucket: 2,
+                max_chain: 3
+            }
+            .to_string(),
+            "bucket 2 already holds 3 pairs"
+        );
+    }
+
+    #[test]
+    fn eq() {
+        let mut a = HashMap::new();
+        let mut b = HashMap::with_capacity(100);
+        for i in 0..20 {
+            a.put(i, i * 2);
+            b.put(19 - i, (19 - i) * 2);
+        }
+        assert!(a == b);
+
+        b.put(7, 0);
+        assert!(a != b);
+
+        b.put(7, 14);
+        b.put(20, 40);
+        assert!(a != b);
+    }
+
+    #[test]
+    fn get_or_default_cloned() {
+        let mut map = HashMap::new();
+        map.put("present", vec![1, 2]);
+
+        assert_eq!(map.get_or_default_cloned(&"present"), vec![1, 2]);
+        assert_eq!(map.get_or_default_cloned(&"absent"), Vec::<i32>::new());
+        assert_eq!(map.size(), 1);
+        assert!(!map.contains_key(&"absent"));
+    }
+
+    #[test]
+    fn len_and_is_empty() {
+        let mut map = HashMap::new();
+        assert!(map.is_empty());
+        assert_eq!(map.len(), 0);
+
+        map.put(1, 1);
+        map.put(2, 2);
+        assert!(!map.is_empty());
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.len(), map.size());
+
+        map.remove(&1);
+        map.remove(&2);
+        assert!(map.is_empty());
+        assert_eq!(map.len(), 0);
+    }
+
+    #[test]
+    fn entries_into_channel() {
+        use std::sync::mpsc;
+        use std::thread;
+
+        let mut map = HashMap::new();
+        for i in 0..50 {
+            map.put(i, i.to_string());
+        }
+        let (tx, rx) = mpsc::channel();
+
+        let producer = thread::spawn(move || map.entries_into_channel(&tx));
+        let mut received: Vec<(i32, String)> = rx.iter().collect();
+        received.sort();
+
+        assert!(producer.join().unwrap().is_ok());
+        assert_eq!(received.len(), 50);
+        for (i, (k, v)) in received.into_iter().enumerate() {
+            assert_eq!(k, i as i32);
+            assert_eq!(v, i.to_string());
+        }
+    }
+
+    #[test]
+    fn clear() {
+        let mut map = HashMap::new();
+        for i in 0..30 {
+            map.put(i, i);
+        }
+        let buckets = map.table.len();
+
+        map.clear();
+
+        assert_eq!(map.size(), 0);
+        assert_eq!(map.table.len(), buckets);
+        assert_eq!(map.capacity, buckets);
+        assert_eq!(map.get(&3), None);
+
+        for i in 0..30 {
+            map.put(i, i + 1);
+        }
+        assert_eq!(map.table.len(), buckets);
+        assert_eq!(map.get(&3), Some(&4));
+        assert_eq!(map.size(), 30);
+    }
+
+    #[test]
+    fn count_where_value() {
+        let mut counts = HashMap::new();
+        for word in "a b a c a b d e a".split(' ') {
+            counts.bump(word, 1);
+        }
+
+        assert_eq!(counts.count_where_value(|n| *n >= 2), 2);
+        assert_eq!(counts.count_where_value(|n| *n > 10), 0);
+        assert_eq!(counts.count_where_value(|_n| true), counts.size());
+    }
+
+    #[test]
+    fn index() {
+        let mut map = HashMap::new();
+        map.put("one", 1);
+
+        assert_eq!(map[&"one"], 1);
+        map[&"one"] += 10;
+        assert_eq!(map[&"one"], 11);
+    }
+
+    #[test]
+    #[should_panic(expected = "no entry found for key")]
+    fn index_missing_key() {
+        let mut map = HashMap::new();
+        map.put("one", 1);
+        let _ = map[&"two"];
+    }
+
+    #[test]
+    fn steal_bucket() {
+        let mut map = HashMap::new();
+        map.put(Collide(1), 1);
+        map.put(Collide(2), 2);
+        map.put(Collide(3), 3);
+        let bucket = map.index_for(&Collide(0)).unwrap();
+
+        let stolen = map.steal_bucket(bucket);
+
+        assert_eq!(map.size(), 0);
+        assert!(map.table[bucket].is_empty());
+        assert!(!map.contains_key(&Collide(2)));
+        let mut pairs: Vec<(i32, i32)> = stolen.into_iter().map(|(k, v)| (k.0, v)).collect();
+        pairs.sort();
+        assert_eq!(pairs, vec![(1, 1), (2, 2), (3, 3)]);
+        assert!(map.steal_bucket(map.table.len()).is_empty());
+    }
+
+    #[test]
+    fn retain() {
+        let mut map = HashMap::new();
+        for i in 0..40 {
+            map.put(i, i);
+        }
+
+        map.retain(|_k, v| *v % 2 == 0);
+
+        let mut kept: Vec<i32> = map.keys().copied().collect();
+        kept.sort();
+        assert_eq!(kept, (0..40).step_by(2).collect::<Vec<_>>());
+        assert_eq!(map.size(), 20);
+        assert_eq!(map.iter().count(), 20);
+    }
+
+    #[test]
+    fn put_all_from_list() {
+        let mut map = HashMap::new();
+        map.put(0, 0);
+        let keys = colliding_keys(&map, 3);
+        for &k in &keys {
+            map.put(k, k);
+        }
+        let bucket = map.index_for(&keys[0]).unwrap();
+
+        let mut stolen = map.steal_bucket(bucket);
+        stolen.iter_mut().for_each(|(_k, v)| *v += 100);
+        let outsider = (1..).find(|k| map.index_for(k) != Some(bucket)).unwrap();
+        stolen.push((outsider, -1));
+        map.put_all_from_list(stolen);
+
+        assert_eq!(map.size(), 4);
+        for &k in &keys {
+            assert_eq!(map.get(&k), Some(&(k + 100)));
+            assert!(map.key_exists_in_bucket_only(&k, bucket));
+        }
+        assert_eq!(map.get(&outsider), Some(&-1));
+        assert!(!map.key_exists_in_bucket_only(&outsider, bucket));
+    }
+
+    #[test]
+    fn from_iter() {
+        let map: HashMap<_, _> = vec![(1, "a"), (2, "b"), (1, "c")].into_iter().collect();
+
+        assert_eq!(map.size(), 2);
+        assert_eq!(map.get(&1), Some(&"c"));
+        assert_eq!(map.get(&2), Some(&"b"));
+
+        let big: HashMap<i32, i32> = (0..1000).map(|i| (i, i)).collect();
+        assert_eq!(big.table.len(), 2048);
+        assert_eq!(big.size(), 1000);
+    }
+
+    #[test]
+    fn from_array() {
+        let map = HashMap::from([("x", 1), ("y", 2), ("x", 3)]);
+
+        assert_eq!(map.size(), 2);
+        assert_eq!(map.get(&"x"), Some(&3));
+        assert_eq!(map.get(&"y"), Some(&2));
+        assert!(HashMap::<i32, i32>::from([]).is_empty());
+    }
+
+    #[test]
+    fn delta_since() {
+        let mut map = HashMap::new();
+        map.put(100, 100);
+        let mark = map.mark();
+
+        for i in 0..5 {
+            map.put(i, i);
+        }
+        map.remove(&0);
+        map.remove(&1);
+
+        assert_eq!(map.delta_since(mark), 3);
+        map.clear();
+        assert_eq!(map.delta_since(mark), -1);
+    }
+
+    #[test]
+    fn extend() {
+        let mut map = HashMap::from([(1, "one"), (2, "two")]);
+
+        map.extend(vec![(2, "deux"), (3, "three"), (4, "four")]);
+        map.extend((5..7).map(|i| (i, "many")));
+
+        assert_eq!(map.size(), 6);
+        assert_eq!(map.get(&1), Some(&"one"));
+        assert_eq!(map.get(&2), Some(&"deux"));
+        assert_eq!(map.get(&3), Some(&"three"));
+        assert_eq!(map.get(&6), Some(&"many"));
+    }
+
+    #[test]
+    fn from_prehashed() {
+        let hasher = RandomState::new();
+        let source = HashMap::<String, usize>::with_hasher(hasher.clone());
+        let triples: Vec<(u64, String, usize)> = (0..30)
+            .map(|i| (source.hash_of(&i.to_string()), i.to_string(), i))
+            .collect();
+
+        let map = HashMap::from_prehashed(triples, hasher);
+
+        assert_eq!(map.size(), 30);
+        assert_eq!(map.table.len(), 64);
+        for i in 0..30 {
+            assert_eq!(map.get(&i.to_string()), Some(&i));
+        }
+        assert!(map.verify_no_duplicate_keys());
+
+        let map = HashMap::from_prehashed(
+            vec![
+                (source.hash_of(&"1".to_string()), "1".to_string(), 1),
+                (source.hash_of(&"1".to_string()), "1".to_string(), 2),
+            ],
+            source.hasher.clone(),
+        );
+        assert_eq!(map.size(), 1);
+        assert_eq!(map.get(&"1".to_string()), Some(&2));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "prehashed with a different hasher")]
+    fn from_prehashed_foreign_hash() {
+        let hasher = RandomState::new();
+        let wrong = hasher.hash_one(1) ^ 1;
+        HashMap::from_prehashed(vec![(wrong, 1, 1)], hasher);
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity overflow")]
+    fn reserve_overflow() {
+        HashMap::<u8, u8>::new().reserve(usize::MAX / 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity overflow")]
+    fn reserve_overflow_with_pairs() {
+        let mut map = HashMap::new();
+        map.put(1, 1);
+        map.reserve(usize::MAX);
+    }
+
+    #[test]
+    fn reserve() {
+        use std::sync::{Arc, Mutex};
+
+        let mut map = HashMap::new();
+        map.reserve(0);
+        assert!(map.table.is_empty());
+        map.reserve(1);
+        assert_eq!(map.table.len(), DEFAULT_CAPACITY);
+
+        map.put(-1, -1);
+        let resizes = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&resizes);
+        map.on_resize(Box::new(move |old, new| {
+            recorded.lock().unwrap().push((old, new))
+        }));
+
+        map.reserve(1000);
+        let buckets = map.table.len();
+        for i in 0..1000 {
+            map.put(i, i);
+        }
+
+        assert_eq!(*resizes.lock().unwrap(), vec![(DEFAULT_CAPACITY, buckets)]);
+        assert_eq!(map.table.len(), buckets);
+        assert_eq!(map.size(), 1001);
+        map.reserve(0);
+        assert_eq!(map.table.len(), buckets);
+    }
+
+    #[test]
+    fn split_by_hash_parity() {
+        let map: HashMap<i32, i32> = (0..100).map(|i| (i, i * 3)).collect();
+        let original = map.clone();
+
+        let (even, odd) = map.split_by_hash_parity();
+
+        assert_eq!(even.size() + odd.size(), 100);
+        assert!(even.keys().all(|k| even.hash_of(k).is_multiple_of(2)));
+        assert!(odd.keys().all(|k| odd.hash_of(k) % 2 == 1));
+        for (k, v) in original.iter() {
+            let half = if original.hash_of(k).is_multiple_of(2) {
+                &even
+            } else {
+                &odd
+            };
+            assert_eq!(half.get(k), Some(v));
+        }
+    }
+
+    #[test]
+    fn get_key_value() {
+        let mut map = HashMap::new();
+        map.put("key".to_string(), 1);
+        let lookup = String::from("key");
+
+        let (stored, value) = map.get_key_value(&lookup).unwrap();
+
+        assert_eq!(stored, &lookup);
+        assert_eq!(value, &1);
+        assert_ne!(stored.as_ptr(), lookup.as_ptr());
+        let (in_table, _v) = map.iter().next().unwrap();
+        assert!(std::ptr::eq(stored, in_table));
+        assert_eq!(map.get_key_value(&"other".to_string()), None);
+    }
+
+    // resize moves the nodes themselves and never goes through put,
+    // so size is left alone however the chains are laid out
+    #[test]
+    fn resize_keeps_size_with_colliding_keys() {
+        let mut map = HashMap::new();
+        map.put(0, 0);
+        let keys = colliding_keys(&map, 6);
+        for &k in &keys {
+            map.put(k, k);
+        }
+        for i in 0..30 {
+            map.put(i + 1000, i);
+        }
+        assert!(map.table.len() > DEFAULT_CAPACITY);
+
+        assert_eq!(map.size(), 36);
+        assert_eq!(map.iter().count(), 36);
+        assert!(keys.iter().all(|k| map.get(k) == Some(k)));
+
+        let mut colliding = HashMap::new();
+        for i in 0..40 {
+            colliding.put(Collide(i), i);
+        }
+        assert_eq!(colliding.size(), 40);
+        assert_eq!(colliding.longest_chain(), 40);
+        assert_eq!(colliding.iter().count(), 40);
+    }
+
+    #[test]
+    fn map_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<HashMap<i32, i32>>();
+    }
+
+    #[test]
+    fn on_resize_only_for_changed_tables() {
+        use std::sync::{Arc, Mutex};
+
+        let mut map = HashMap::with_hasher(RandomState::new());
+        map.replace_table_with_capacity(64);
+        for k in colliding_keys(&map, 6) {
+            map.put(k, k);
+        }
+        let resizes = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&resizes);
+        map.on_resize(Box::new(move |old, new| {
+            recorded.lock().unwrap().push((old, new))
+        }));
+
+        assert!(map.auto_rehash_if_skewed(0.1));
+        map.replace_table_with_capacity(64);
+        assert!(resizes.lock().unwrap().is_empty());
+
+        let chosen = map.optimize_capacity(&[7, 11, 13]);
+
+        assert_eq!(*resizes.lock().unwrap(), vec![(64, chosen)]);
+    }
+
+    #[test]
+    fn defragment_keeps_hits() {
+        let mut map = HashMap::new();
+        for i in 0..10 {
+            map.put(i, i);
+        }
+        map.set_hit_tracking(true);
+        map.put(10, 10);
+        map.get(&3);
+        map.get(&3);
+        map.get(&10);
+
+        map.defragment();
+
+        assert_eq!(map.hot_keys(2), vec![&3, &10]);
+        map.set_hit_tracking(false);
+        map.get(&10);
+        map.get(&10);
+        assert_eq!(map.hot_keys(2), vec![&3, &10]);
+    }
+
+    #[test]
+    fn into_iter() {
+        let mut map = HashMap::new();
+        map.put("one", 1);
+        map.put("two", 2);
+        map.put("three", 3);
+
+        let pairs: Vec<&(&str, i32)> = (&map).into_iter().collect();
+        assert_eq!(pairs.len(), 3);
+
+        let mut res: Vec<(&str, i32)> = map.into_iter().collect();
+        res.sort();
+
+        assert_eq!(res, vec![("one", 1), ("three", 3), ("two", 2)]);
+    }
 }
@@ -1,8 +1,11 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 type Link<T> = Option<Box<Node<T>>>;
 
 #[derive(Debug)]
 pub struct LinkedList<T> {
-    pub head: Link<T>,
+    head: Link<T>,
     size: usize,
 }
 
@@ -15,19 +18,38 @@ impl<T> LinkedList<T> {
     }
 
     pub fn push(&mut self, el: T) {
+        self.push_node(Box::new(Node::new(el)));
+    }
+
+    // the node gets its bookkeeping allocated, starting at `stamp` and no hits
+    pub(crate) fn push_stamped(&mut self, el: T, stamp: u64) {
+        self.push_with_meta(el, Some(NodeMeta::new(stamp)));
+    }
+
+    pub(crate) fn push_with_meta(&mut self, el: T, meta: Option<NodeMeta>) {
         let mut new_node = Box::new(Node::new(el));
-        new_node.next = self.head.take();
-        self.head = Some(new_node);
-        self.size += 1;
+        new_node.meta = meta.map(Box::new);
+        self.push_node(new_node);
     }
 
     pub fn pop(&mut self) -> Option<T> {
-        self.head.take().map(|node| {
-            self.head = node.next;
+        self.pop_node().map(|node| node.element)
+    }
+
+    // moving whole nodes between lists keeps their bookkeeping and allocations
+    pub(crate) fn push_node(&mut self, mut node: Box<Node<T>>) {
+        node.next = self.head.take();
+        self.head = Some(node);
+        self.size += 1;
+    }
+
+    pub(crate) fn pop_node(&mut self) -> Option<Box<Node<T>>> {
+        self.head.take().map(|mut node| {
+            self.head = node.next.take();
             if self.size > 0 {
                 self.size -= 1;
             }
-            node.element
+            node
         })
     }
 
@@ -39,23 +61,162 @@ impl<T> LinkedList<T> {
         self.head.as_mut().map(|node| &mut node.element)
     }
 
+    // only elements are reachable through the nodes, so walking them can't break `size`
+    pub fn head_node_mut(&mut self) -> Option<&mut Node<T>> {
+        self.head.as_deref_mut()
+    }
+
+    // reverses the chain in place by relinking the existing nodes
+    pub fn reverse(&mut self) {
+        let mut reversed: Link<T> = None;
+        let mut cur_link = self.head.take();
+        while let Some(mut node) = cur_link {
+            cur_link = node.next.take();
+            node.next = reversed;
+            reversed = Some(node);
+        }
+        self.head = reversed;
+    }
+
     pub fn size(&self) -> usize {
         self.size
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    // everything before the first matching element goes to the first list,
+    // the matching element and the rest of the list to the second one
+    pub fn split_at_value<F: Fn(&T) -> bool>(mut self, f: F) -> (LinkedList<T>, LinkedList<T>) {
+        let mut before = Vec::new();
+        while let Some(el) = self.pop() {
+            if f(&el) {
+                self.push(el);
+                break;
+            }
+            before.push(el);
+        }
+        (LinkedList::from_vec(before), self)
+    }
+
+    // builds a list which iterates in the same order as the vec
+    fn from_vec(mut elements: Vec<T>) -> Self {
+        let mut list = LinkedList::new();
+        while let Some(el) = elements.pop() {
+            list.push(el);
+        }
+        list
+    }
+
+    // keeps only the elements for which `f` returns true, `f` may modify them on the way
+    pub fn retain_mut<F: FnMut(&mut T) -> bool>(&mut self, mut f: F) {
+        let mut cur_link = &mut self.head;
+        while cur_link.is_some() {
+            if f(&mut cur_link.as_mut().unwrap().element) {
+                cur_link = &mut cur_link.as_mut().unwrap().next;
+            } else {
+                let removed = cur_link.take().unwrap();
+                *cur_link = removed.next;
+                self.size = self.size.saturating_sub(1);
+            }
+        }
+    }
+
+    // unlinks and returns the first element matching `f`
+    pub fn remove_first<F: Fn(&T) -> bool>(&mut self, f: F) -> Option<T> {
+        let mut cur_link = &mut self.head;
+        while cur_link.as_ref().is_some_and(|node| !f(&node.element)) {
+            cur_link = &mut cur_link.as_mut().unwrap().next;
+        }
+        let mut removed = cur_link.take()?;
+        *cur_link = removed.next.take();
+        self.size = self.size.saturating_sub(1);
+        Some(removed.element)
+    }
+
+    pub fn find_map<U, F: Fn(&T) -> Option<U>>(&self, f: F) -> Option<U> {
+        self.iter().find_map(f)
+    }
+
+    // unlinks and returns the element at position `pos`, counting from the head
+    pub fn remove_at(&mut self, pos: usize) -> Option<T> {
+        let mut cur_link = &mut self.head;
+        for _ in 0..pos {
+            cur_link = &mut cur_link.as_mut()?.next;
+        }
+        let mut removed = cur_link.take()?;
+        *cur_link = removed.next.take();
+        self.size = self.size.saturating_sub(1);
+        Some(removed.element)
+    }
+
+    // merges two lists sorted ascending from head to tail into one such list;
+    // on equal elements the one from `self` comes first
+    pub fn merge_sorted(mut self, mut other: LinkedList<T>) -> LinkedList<T>
+    where
+        T: Ord,
+    {
+        let mut merged = Vec::with_capacity(self.size + other.size);
+        loop {
+            let take_self = match (self.peek(), other.peek()) {
+                (Some(a), Some(b)) => a <= b,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            let el = if take_self { self.pop() } else { other.pop() };
+            merged.extend(el);
+        }
+        LinkedList::from_vec(merged)
+    }
+
+    // elements from head to tail
+    pub fn into_vec(self) -> Vec<T> {
+        self.into_iter().collect()
+    }
+
+    // the elements from head to tail in groups of `n`, the last group may be shorter
+    pub fn chunks(&self, n: usize) -> impl Iterator<Item = Vec<&T>> {
+        assert!(n > 0, "chunk size must be positive");
+        let mut iter = self.iter();
+        std::iter::from_fn(move || {
+            let chunk: Vec<&T> = iter.by_ref().take(n).collect();
+            (!chunk.is_empty()).then_some(chunk)
+        })
+    }
+
+    // pairs the elements positionally from the heads and keeps the results in that
+    // order, the elements past the end of the shorter list are dropped
+    pub fn zip_with<U, W, F: Fn(T, U) -> W>(self, other: LinkedList<U>, f: F) -> LinkedList<W> {
+        let zipped = self
+            .into_iter()
+            .zip(other.into_iter())
+            .map(|(a, b)| f(a, b))
+            .collect();
+        LinkedList::from_vec(zipped)
+    }
+
     pub fn into_iter(self) -> IntoIter<T> {
         IntoIter(self)
     }
 
-    pub fn iter(&self) -> Iter<T> {
+    pub fn iter(&self) -> Iter<'_, T> {
         Iter {
             next: self.head.as_deref(),
+            remaining: self.size,
+            buffered: VecDeque::new(),
         }
     }
 
-    pub fn iter_mut(&mut self) -> IterMut<T> {
+    pub fn nodes(&self) -> impl Iterator<Item = &Node<T>> {
+        std::iter::successors(self.head.as_deref(), |node| node.next.as_deref())
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
         IterMut {
             next: self.head.as_deref_mut(),
+            remaining: self.size,
         }
     }
 }
@@ -67,32 +228,99 @@ impl<T> Iterator for IntoIter<T> {
     fn next(&mut self) -> Option<Self::Item> {
         self.0.pop()
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.size, Some(self.0.size))
+    }
+
+    fn count(self) -> usize {
+        self.0.size
+    }
 }
+impl<T> ExactSizeIterator for IntoIter<T> {}
 
 pub struct Iter<'a, T> {
     next: Option<&'a Node<T>>,
+    // amount of nodes not yielded yet, taken from the list's size
+    remaining: usize,
+    // the not yet yielded elements once next_back was called, see below
+    buffered: VecDeque<&'a T>,
 }
 impl<'a, T> Iterator for Iter<'a, T> {
     type Item = &'a T;
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(el) = self.buffered.pop_front() {
+            self.remaining = self.remaining.saturating_sub(1);
+            return Some(el);
+        }
         self.next.map(|node| {
             self.next = node.next.as_deref();
+            self.remaining = self.remaining.saturating_sub(1);
             &node.element
         })
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+
+    fn count(self) -> usize {
+        self.remaining
+    }
+}
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+// the nodes only link forward, so the first next_back walks all the remaining ones
+// into a buffer: O(n) time and memory once, every later call from either end is O(1)
+impl<T> DoubleEndedIterator for Iter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.next {
+            self.next = node.next.as_deref();
+            self.buffered.push_back(&node.element);
+        }
+        let el = self.buffered.pop_back()?;
+        self.remaining = self.remaining.saturating_sub(1);
+        Some(el)
+    }
 }
 
 pub struct IterMut<'a, T> {
     next: Option<&'a mut Node<T>>,
+    remaining: usize,
 }
 impl<'a, T> Iterator for IterMut<'a, T> {
     type Item = &'a mut T;
     fn next(&mut self) -> Option<Self::Item> {
         self.next.take().map(|node| {
             self.next = node.next.as_deref_mut();
+            self.remaining = self.remaining.saturating_sub(1);
             &mut node.element
         })
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+
+    fn count(self) -> usize {
+        self.remaining
+    }
+}
+impl<T> ExactSizeIterator for IterMut<'_, T> {}
+
+// push prepends, so the copies are pushed tail first to keep the order;
+// the stamps and hit counts are copied along
+impl<T: Clone> Clone for LinkedList<T> {
+    fn clone(&self) -> Self {
+        let mut list = LinkedList::new();
+        for node in self.nodes().collect::<Vec<_>>().into_iter().rev() {
+            let meta = node.meta.as_ref().map(|meta| NodeMeta {
+                stamp: meta.stamp,
+                hits: AtomicUsize::new(meta.hits.load(Ordering::Relaxed)),
+            });
+            list.push_with_meta(node.element.clone(), meta);
+        }
+        list
+    }
 }
 
 impl<T> Drop for LinkedList<T> {
@@ -108,6 +336,26 @@ impl<T> Drop for LinkedList<T> {
 pub struct Node<T> {
     element: T,
     next: Option<Box<Node<T>>>,
+    // only allocated for nodes whose owner asked for it, the others carry an empty pointer
+    meta: Option<Box<NodeMeta>>,
+}
+
+// bookkeeping kept for the owner of the list
+#[derive(Debug)]
+pub(crate) struct NodeMeta {
+    // e.g. an insertion sequence number
+    stamp: u64,
+    // read counter the owner may bump through a shared reference
+    hits: AtomicUsize,
+}
+
+impl NodeMeta {
+    fn new(stamp: u64) -> Self {
+        Self {
+            stamp,
+            hits: AtomicUsize::new(0),
+        }
+    }
 }
 
 impl<T> Node<T> {
@@ -115,8 +363,49 @@ impl<T> Node<T> {
         Self {
             element: el,
             next: None,
+            meta: None,
+        }
+    }
+
+    pub fn element(&self) -> &T {
+        &self.element
+    }
+
+    pub(crate) fn into_parts(self) -> (T, Option<NodeMeta>) {
+        (self.element, self.meta.map(|meta| *meta))
+    }
+
+    // 0 for nodes without bookkeeping
+    pub fn stamp(&self) -> u64 {
+        self.meta.as_ref().map_or(0, |meta| meta.stamp)
+    }
+
+    // 0 for nodes without bookkeeping
+    pub fn hits(&self) -> usize {
+        self.meta
+            .as_ref()
+            .map_or(0, |meta| meta.hits.load(Ordering::Relaxed))
+    }
+
+    // does nothing for nodes without bookkeeping, see `track`
+    pub(crate) fn hit(&self) {
+        if let Some(meta) = self.meta.as_ref() {
+            meta.hits.fetch_add(1, Ordering::Relaxed);
         }
     }
+
+    // allocates the bookkeeping if the node has none yet
+    pub(crate) fn track(&mut self) {
+        self.meta.get_or_insert_with(|| Box::new(NodeMeta::new(0)));
+    }
+
+    pub fn element_mut(&mut self) -> &mut T {
+        &mut self.element
+    }
+
+    pub fn next_mut(&mut self) -> Option<&mut Node<T>> {
+        self.next.as_deref_mut()
+    }
 }
 
 #[cfg(test)]
@@ -147,9 +436,180 @@ mod test {
         assert_eq!(list.size(), 0);
 
         list.push(66);
-        let mut new_value = list.peek_mut().unwrap();
+        let new_value = list.peek_mut().unwrap();
         *new_value = 55;
 
         assert_eq!(list.pop(), Some(55));
     }
+
+    #[test]
+    fn exact_size_iter() {
+        let list = LinkedList::from_vec(vec![1, 2, 3, 4]);
+        let mut iter = list.iter();
+        assert_eq!(iter.len(), 4);
+
+        iter.next();
+
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.size_hint(), (3, Some(3)));
+        assert_eq!(iter.count(), 3);
+        assert_eq!(list.into_iter().len(), 4);
+    }
+
+    #[test]
+    fn split_at_value() {
+        let list = LinkedList::from_vec(vec![1, 2, 3, 4]);
+
+        let (before, after) = list.split_at_value(|el| el % 2 == 0);
+
+        assert_eq!(before.iter().collect::<Vec<_>>(), vec![&1]);
+        assert_eq!(before.size(), 1);
+        assert_eq!(after.iter().collect::<Vec<_>>(), vec![&2, &3, &4]);
+        assert_eq!(after.size(), 3);
+    }
+
+    #[test]
+    fn retain_mut() {
+        let mut list = LinkedList::from_vec(vec![1, 2, 3, 4, 5]);
+
+        list.retain_mut(|el| {
+            *el += 1;
+            *el > 3
+        });
+
+        assert_eq!(list.size(), 3);
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![&4, &5, &6]);
+    }
+
+    #[test]
+    fn head_node_mut() {
+        let mut list = LinkedList::from_vec(vec![1, 2, 3]);
+
+        let head = list.head_node_mut().unwrap();
+        *head.next_mut().unwrap().element_mut() = 20;
+
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![&1, &20, &3]);
+        assert_eq!(list.size(), 3);
+        assert!(LinkedList::<i32>::new().head_node_mut().is_none());
+    }
+
+    #[test]
+    fn find_map() {
+        let list = LinkedList::from_vec(vec!["one", "two", "3", "four", "5"]);
+
+        assert_eq!(list.find_map(|s| s.parse::<i32>().ok()), Some(3));
+        assert_eq!(
+            LinkedList::<&str>::new().find_map(|s| s.parse::<i32>().ok()),
+            None
+        );
+    }
+
+    #[test]
+    fn merge_sorted() {
+        let odd = LinkedList::from_vec(vec![1, 3, 5]);
+        let even = LinkedList::from_vec(vec![2, 4, 6]);
+
+        let merged = odd.merge_sorted(even);
+
+        assert_eq!(
+            merged.iter().collect::<Vec<_>>(),
+            vec![&1, &2, &3, &4, &5, &6]
+        );
+        assert_eq!(merged.size(), 6);
+    }
+
+    #[test]
+    fn into_vec() {
+        let list = LinkedList::from_vec(vec!["a", "b", "c"]);
+
+        // the list is moved into the call, using it afterwards wouldn't compile
+        let elements = list.into_vec();
+
+        assert_eq!(elements, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn chunks() {
+        let list = LinkedList::from_vec(vec![1, 2, 3, 4, 5]);
+
+        let chunks: Vec<Vec<&i32>> = list.chunks(2).collect();
+
+        assert_eq!(chunks, vec![vec![&1, &2], vec![&3, &4], vec![&5]]);
+        assert_eq!(LinkedList::<i32>::new().chunks(2).count(), 0);
+    }
+
+    #[test]
+    fn clone() {
+        let mut list = LinkedList::new();
+        list.push_stamped("c", 3);
+        list.push_stamped("b", 2);
+        list.push_stamped("a", 1);
+
+        let mut copy = list.clone();
+        list.pop();
+
+        assert_eq!(copy.iter().collect::<Vec<_>>(), vec![&"a", &"b", &"c"]);
+        assert_eq!(
+            copy.nodes().map(|n| n.stamp()).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(copy.size(), 3);
+        copy.push("z");
+        assert_eq!(list.size(), 2);
+    }
+
+    #[test]
+    fn is_empty() {
+        let mut list = LinkedList::new();
+        assert!(list.is_empty());
+
+        list.push(1);
+        assert!(!list.is_empty());
+
+        list.pop();
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn zip_with() {
+        let a = LinkedList::from_vec(vec![1, 2, 3]);
+        let b = LinkedList::from_vec(vec![10, 20]);
+
+        let sums = a.zip_with(b, |x, y| x + y);
+
+        assert_eq!(sums.iter().collect::<Vec<_>>(), vec![&11, &22]);
+        assert_eq!(sums.size(), 2);
+    }
+
+    #[test]
+    fn iter_rev() {
+        let list = LinkedList::from_vec(vec![1, 2, 3, 4]);
+
+        assert_eq!(list.iter().rev().collect::<Vec<_>>(), vec![&4, &3, &2, &1]);
+
+        let mut iter = list.iter();
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next_back(), Some(&4));
+        assert_eq!(iter.len(), 2);
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next_back(), Some(&3));
+        assert_eq!(iter.next_back(), None);
+        assert_eq!(iter.next(), None);
+        assert_eq!(LinkedList::<i32>::new().iter().next_back(), None);
+    }
+
+    #[test]
+    fn meta_only_on_request() {
+        let mut list = LinkedList::new();
+        list.push(1);
+        list.push_stamped(2, 7);
+        list.head_node_mut().unwrap().hit();
+
+        let nodes: Vec<_> = list.nodes().collect();
+        assert!(nodes[1].meta.is_none());
+        assert_eq!((nodes[1].stamp(), nodes[1].hits()), (0, 0));
+        nodes[1].hit();
+        assert_eq!(nodes[1].hits(), 0);
+        assert_eq!((nodes[0].stamp(), nodes[0].hits()), (7, 1));
+    }
 }